@@ -1,116 +1,260 @@
-use rand::prelude::*;
 use specs::prelude::*;
 
 use crate::components::*;
+use crate::config::GameMode;
+use crate::neighbourhood::Neighbourhood;
+use crate::steering::{self, Agent, Behaviour, Body, Circle, Target, WeightedBehaviour};
+use crate::vector::Vector2;
+use crate::GameRng;
 
-const ENEMY_MOVEMENT_SPEED: i32 = 8;
-const HERO_MOVEMENT_SPEED: i32 = 3;
+pub const ENEMY_MOVEMENT_SPEED: f32 = 8.0;
+pub const CHASER_MOVEMENT_SPEED: f32 = 2.5;
+pub const HERO_MOVEMENT_SPEED: f32 = 3.0;
+/// Distance within which a flock scatters from the hero
+const FLOCK_PANIC_RADIUS: f32 = 120.0;
+
+/// How the hero steers under the given game rules, always steering around obstacles
+pub fn hero_steering(mode: GameMode) -> Steering {
+    use self::Behaviour::*;
+    let behaviours = match mode {
+        // Approach the nearest prey
+        GameMode::Hunt => vec![
+            WeightedBehaviour::new(Arrive(Target::Prey), 1.0),
+            WeightedBehaviour::new(AvoidObstacles, 2.0),
+        ],
+        // Keep away from chasers, otherwise idle about
+        GameMode::Tag => vec![
+            WeightedBehaviour::new(Evade(Target::Chaser), 1.0),
+            WeightedBehaviour::new(Wander, 0.2),
+            WeightedBehaviour::new(AvoidObstacles, 2.0),
+        ],
+        GameMode::Mixed => vec![
+            WeightedBehaviour::new(Arrive(Target::Prey), 1.0),
+            WeightedBehaviour::new(Evade(Target::Chaser), 1.5),
+            WeightedBehaviour::new(AvoidObstacles, 2.0),
+        ],
+    };
+    Steering::new(behaviours, HERO_MOVEMENT_SPEED, HERO_MOVEMENT_SPEED)
+}
+
+/// How an enemy in the given role steers, either independently or as part of a flock
+pub fn enemy_steering(role: EnemyRole, flocking: bool) -> Steering {
+    use self::Behaviour::*;
+    match (role, flocking) {
+        // Jitter around randomly, drifting away from the hero once it's spotted
+        (EnemyRole::Prey, false) => Steering::new(
+            vec![
+                WeightedBehaviour::new(Wander, 1.0),
+                WeightedBehaviour::new(Evade(Target::Hero), 0.3),
+                WeightedBehaviour::new(AvoidObstacles, 1.0),
+                WeightedBehaviour::new(Separation, 0.5),
+            ],
+            ENEMY_MOVEMENT_SPEED,
+            ENEMY_MOVEMENT_SPEED,
+        ),
+        // Move as a herd, scattering away from the hero once it's spotted
+        (EnemyRole::Prey, true) => Steering::new(
+            vec![
+                WeightedBehaviour::new(Separation, 1.5),
+                WeightedBehaviour::new(Alignment, 1.0),
+                WeightedBehaviour::new(Cohesion, 1.0),
+                WeightedBehaviour::new(FleeWithin(Target::Hero, FLOCK_PANIC_RADIUS), 2.0),
+                WeightedBehaviour::new(AvoidObstacles, 2.0),
+                WeightedBehaviour::new(Wander, 0.3),
+            ],
+            ENEMY_MOVEMENT_SPEED / 2.0,
+            1.0,
+        ),
+        // Prowl around until the hero is spotted, then run it down
+        (EnemyRole::Chaser, false) => Steering::new(
+            vec![
+                WeightedBehaviour::new(Pursue(Target::Hero), 1.0),
+                WeightedBehaviour::new(Wander, 0.5),
+                WeightedBehaviour::new(AvoidObstacles, 2.0),
+                WeightedBehaviour::new(Separation, 0.5),
+            ],
+            CHASER_MOVEMENT_SPEED,
+            0.5,
+        ),
+        // Hunt as a pack
+        (EnemyRole::Chaser, true) => Steering::new(
+            vec![
+                WeightedBehaviour::new(Pursue(Target::Hero), 1.0),
+                WeightedBehaviour::new(Separation, 1.5),
+                WeightedBehaviour::new(Alignment, 0.5),
+                WeightedBehaviour::new(Cohesion, 0.5),
+                WeightedBehaviour::new(AvoidObstacles, 2.0),
+                WeightedBehaviour::new(Wander, 0.3),
+            ],
+            CHASER_MOVEMENT_SPEED,
+            0.5,
+        ),
+    }
+}
+
+/// The body of the given kind nearest to `position`, if there are any
+fn nearest(bodies: &[(Entity, Body)], position: Vector2) -> Option<&(Entity, Body)> {
+    bodies.iter().min_by(|(_, a), (_, b)| {
+        let a_distance = a.position.distance(position);
+        let b_distance = b.position.distance(position);
+        a_distance.total_cmp(&b_distance)
+    })
+}
 
 pub struct AI;
 
+/**
+ * Steer every AI controlled entity according to its weighted steering behaviours
+ */
 impl<'a> System<'a> for AI {
     type SystemData = (
+        Entities<'a>,
         ReadStorage<'a, AIControlled>,
         ReadStorage<'a, Enemy>,
+        ReadStorage<'a, EnemyRole>,
         ReadStorage<'a, Hero>,
+        ReadStorage<'a, Perception>,
         ReadStorage<'a, Position>,
+        ReadStorage<'a, Obstacle>,
+        WriteExpect<'a, GameRng>,
+        WriteStorage<'a, Steering>,
         WriteStorage<'a, Velocity>,
     );
 
-    fn run(&mut self, mut data: Self::SystemData) {
-        //TODO: This code can be made nicer and more idiomatic using more pattern matching.
-        // Look up "rust irrefutable patterns" and use them here.
-        let mut rng = thread_rng();
-        for (_, _, vel) in (&data.0, &data.1, &mut data.4).join() {
-            if rng.gen_range(0..2) == 0 {
-                vel.speed = ENEMY_MOVEMENT_SPEED;
-                vel.direction = match rng.gen_range(0..4) {
-                    0 => Direction::Up,
-                    1 => Direction::Down,
-                    2 => Direction::Left,
-                    3 => Direction::Right,
-                    _ => unreachable!(),
-                }
-            }
+    fn run(
+        &mut self,
+        (
+            entities,
+            ai_controlled,
+            enemies,
+            roles,
+            heroes,
+            perceptions,
+            positions,
+            obstacles,
+            mut rng,
+            mut steerings,
+            mut velocities,
+        ): Self::SystemData,
+    ) {
+        let bodies_of = |kind: &dyn Fn(Entity) -> bool| -> Vec<(Entity, Body)> {
+            (&entities, &positions, &velocities)
+                .join()
+                .filter(|(entity, _, _)| kind(*entity))
+                .map(|(entity, pos, vel)| {
+                    let body = Body {
+                        position: pos.0,
+                        velocity: vel.0,
+                    };
+                    (entity, body)
+                })
+                .collect()
+        };
+        let is_role = |entity, role| roles.get(entity) == Some(&role);
+        let prey_bodies = bodies_of(&|entity| is_role(entity, EnemyRole::Prey));
+        let chaser_bodies = bodies_of(&|entity| is_role(entity, EnemyRole::Chaser));
+        let hero_bodies = bodies_of(&|entity| heroes.contains(entity));
+        let mut neighbourhood = Neighbourhood::new(steering::NEIGHBOURHOOD_RADIUS);
+        for (entity, body) in bodies_of(&|entity| steerings.contains(entity)) {
+            neighbourhood.insert(entity, body);
         }
+        let obstacle_circles: Vec<Circle> = (&positions, &obstacles)
+            .join()
+            .map(|(pos, obstacle)| Circle {
+                center: pos.0,
+                radius: obstacle.radius,
+            })
+            .collect();
 
-        for (_, _, hero_pos, hero_vel) in (&data.0, &data.2, &data.3, &mut data.4).join() {
-            let hero_x = hero_pos.0.x;
-            let hero_y = hero_pos.0.y;
-
-            // Find the nearest enemy
-            let mut nearest_enemy_pos: Option<Position> = None;
-            let mut nearest_distance = f32::MAX;
-            for (_, enemy_pos) in (&data.1, &data.3).join() {
-                let enemy_x = enemy_pos.0.x;
-                let enemy_y = enemy_pos.0.y;
-
-                let x_delta = (enemy_x - hero_x).abs();
-                let y_delta = (enemy_y - hero_y).abs();
-                let c_squared = (x_delta.pow(2) + y_delta.pow(2)) as f32;
-                let distance = c_squared.sqrt();
-
-                match nearest_enemy_pos {
-                    None | _ if distance < nearest_distance => {
-                        nearest_enemy_pos = Some(enemy_pos.clone());
-                        nearest_distance = distance;
-                    }
-                    _ => {
-                        // no-op (not nearer)
+        for (entity, _, pos, steer, vel) in (
+            &entities,
+            &ai_controlled,
+            &positions,
+            &mut steerings,
+            &mut velocities,
+        )
+            .join()
+        {
+            let agent = Agent {
+                position: pos.0,
+                velocity: vel.0,
+                max_speed: steer.max_speed,
+            };
+            // Entities without perception are all-knowing
+            let perceived_heroes: Vec<(Entity, Body)> = match perceptions.get(entity) {
+                Some(perception) => hero_bodies
+                    .iter()
+                    .filter(|(_, hero)| {
+                        hero.position.distance(agent.position) <= perception.radius
+                            && (!perception.line_of_sight
+                                || steering::line_of_sight(
+                                    agent.position,
+                                    hero.position,
+                                    &obstacle_circles,
+                                ))
+                    })
+                    .cloned()
+                    .collect(),
+                None => hero_bodies.clone(),
+            };
+            let mut targets = Vec::new();
+            let mut target_of = |target: Target| {
+                let found = match target {
+                    Target::Prey => nearest(&prey_bodies, agent.position),
+                    Target::Chaser => nearest(&chaser_bodies, agent.position),
+                    Target::Hero => nearest(&perceived_heroes, agent.position),
+                };
+                if let Some(&(target_entity, _)) = found {
+                    if !targets.contains(&(target, target_entity)) {
+                        targets.push((target, target_entity));
                     }
                 }
-            }
+                found.map(|(_, body)| body)
+            };
+            // Flock mates are nearby steering entities of the same kind
+            let is_enemy = enemies.contains(entity);
+            let neighbours: Vec<Body> = neighbourhood
+                .query(agent.position, steering::NEIGHBOURHOOD_RADIUS)
+                .into_iter()
+                .filter(|(neighbour, _)| {
+                    *neighbour != entity && enemies.contains(*neighbour) == is_enemy
+                })
+                .map(|(_, body)| body)
+                .collect();
 
-            match nearest_enemy_pos {
-                Some(nearest_enemy_pos) => {
-                    let enemy_x = nearest_enemy_pos.0.x;
-                    let enemy_y = nearest_enemy_pos.0.y;
-                    let x_delta = enemy_x - hero_x;
-                    let x_delta_abs = x_delta.abs();
-                    let y_delta = enemy_y - hero_y;
-                    let y_delta_abs = y_delta.abs();
-                    match x_delta_abs >= y_delta_abs {
-                        true if x_delta_abs > 0 => {
-                            // head in the X
-                            hero_vel.speed = match x_delta_abs {
-                                0..=HERO_MOVEMENT_SPEED => x_delta_abs,
-                                _ => HERO_MOVEMENT_SPEED,
-                            };
-                            match x_delta {
-                                i32::MIN..=-1 => {
-                                    hero_vel.direction = Direction::Left;
-                                }
-                                0..=i32::MAX => {
-                                    hero_vel.direction = Direction::Right;
-                                }
-                            }
-                        }
-                        false if y_delta_abs > 0 => {
-                            // head in the Y
-                            hero_vel.speed = match y_delta_abs {
-                                0..=HERO_MOVEMENT_SPEED => y_delta_abs,
-                                _ => HERO_MOVEMENT_SPEED,
-                            };
-                            match y_delta {
-                                i32::MIN..=-1 => {
-                                    hero_vel.direction = Direction::Up;
-                                }
-                                0..=i32::MAX => {
-                                    hero_vel.direction = Direction::Down;
-                                }
-                            }
-                        }
-                        _ => {
-                            // Stop Moving
-                            hero_vel.speed = 0;
-                        }
-                    }
-                }
-                None => {
-                    // Stop Moving
-                    hero_vel.speed = 0;
-                }
+            let mut force = Vector2::ZERO;
+            for weighted in &steer.behaviours {
+                use self::Behaviour::*;
+                let behaviour_force = match weighted.behaviour {
+                    Seek(target) => target_of(target).map_or(Vector2::ZERO, |target| {
+                        steering::seek(&agent, target.position)
+                    }),
+                    // With nothing to arrive at, come to rest where we are
+                    Arrive(target) => target_of(target).map_or(-agent.velocity, |target| {
+                        steering::arrive(&agent, target.position)
+                    }),
+                    Pursue(target) => target_of(target)
+                        .map_or(Vector2::ZERO, |target| steering::pursue(&agent, target)),
+                    Flee(target) => target_of(target).map_or(Vector2::ZERO, |threat| {
+                        steering::flee(&agent, threat.position)
+                    }),
+                    FleeWithin(target, radius) => target_of(target)
+                        .map_or(Vector2::ZERO, |threat| {
+                            steering::flee_within(&agent, threat.position, radius)
+                        }),
+                    Evade(target) => target_of(target)
+                        .map_or(Vector2::ZERO, |threat| steering::evade(&agent, threat)),
+                    Wander => steering::wander(&agent, &mut steer.wander_angle, &mut rng.0),
+                    AvoidObstacles => steering::avoid_obstacles(&agent, &obstacle_circles),
+                    Separation => steering::separation(&agent, &neighbours),
+                    Alignment => steering::alignment(&agent, &neighbours),
+                    Cohesion => steering::cohesion(&agent, &neighbours),
+                };
+                force += behaviour_force * weighted.weight;
             }
+            steer.targets = targets;
+
+            vel.0 = (vel.0 + force.truncate(steer.max_force)).truncate(steer.max_speed);
         }
     }
 }
@@ -16,11 +16,11 @@ impl<'a> System<'a> for Animator {
         //TODO: This code can be made nicer and more idiomatic using more pattern matching.
         // Look up "rust irrefutable patterns" and use them here.
         for (anim, sprite, vel) in (&mut data.0, &mut data.1, &data.2).join() {
-            if vel.speed == 0 {
+            if vel.0.is_zero() {
                 continue;
             }
 
-            let frames = match vel.direction {
+            let frames = match Direction::from_heading(vel.0) {
                 Left => &anim.left_frames,
                 Right => &anim.right_frames,
                 Up => &anim.up_frames,
@@ -0,0 +1,134 @@
+use rand::prelude::*;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+use crate::components::EnemyRole;
+use crate::config::GameMode;
+use crate::prefab::{Prefab, Prefabs};
+use crate::sprite::Spritesheets;
+
+/// The prefab archetypes are based on, unless they name another
+const ENEMY_PREFAB: &str = "enemy";
+
+/// A kind of enemy, as designed in a data file
+#[derive(Debug, Clone, Deserialize)]
+pub struct Archetype {
+    pub name: String,
+    /// How likely this kind is to be spawned, relative to the others
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+    /// The enemy's components, overriding the enemy prefab. Its role is the behaviour model.
+    #[serde(flatten)]
+    pub prefab: Prefab,
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+impl Archetype {
+    pub fn role(&self) -> EnemyRole {
+        self.prefab
+            .role
+            .expect("archetypes are checked for a role when loaded")
+    }
+
+    /// Whether this kind of enemy can appear in the game mode
+    fn suits(&self, mode: GameMode) -> bool {
+        match mode {
+            GameMode::Hunt => self.role() == EnemyRole::Prey,
+            GameMode::Tag => self.role() == EnemyRole::Chaser,
+            GameMode::Mixed => true,
+        }
+    }
+}
+
+/// Every kind of enemy which can be spawned
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Archetypes {
+    pub archetypes: Vec<Archetype>,
+}
+
+impl Archetypes {
+    /// The original enemies: plain enemies fleeing from the hero, and red ones hunting it
+    fn builtin(prefabs: &Prefabs) -> Result<Archetypes, String> {
+        let mut chaser_sprite = prefabs.get(ENEMY_PREFAB)?.sprite.clone();
+        if let Some(layout) = &mut chaser_sprite {
+            layout.tint = Some((255, 96, 96));
+        }
+        Ok(Archetypes {
+            archetypes: vec![
+                Archetype {
+                    name: "reaper".to_string(),
+                    weight: 1.0,
+                    prefab: Prefab {
+                        role: Some(EnemyRole::Prey),
+                        ..Prefab::default()
+                    },
+                },
+                Archetype {
+                    name: "red reaper".to_string(),
+                    weight: 1.0,
+                    prefab: Prefab {
+                        role: Some(EnemyRole::Chaser),
+                        damage: Some(1),
+                        sprite: chaser_sprite,
+                        ..Prefab::default()
+                    },
+                },
+            ],
+        })
+    }
+
+    /// Load enemy kinds from a TOML file, or use the built-in kinds without one, merging each
+    /// with the prefab it's based on
+    pub fn load(
+        path: Option<&Path>,
+        prefabs: &Prefabs,
+        spritesheets: &mut Spritesheets,
+    ) -> Result<Archetypes, String> {
+        let mut archetypes = match path {
+            Some(path) => {
+                let text =
+                    fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+                toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?
+            }
+            None => Archetypes::builtin(prefabs)?,
+        };
+        for archetype in &mut archetypes.archetypes {
+            if archetype.weight < 0.0 {
+                return Err(format!("{} has a negative weight", archetype.name));
+            }
+            if archetype.prefab.base.is_none() {
+                archetype.prefab.base = Some(ENEMY_PREFAB.to_string());
+            }
+            archetype.prefab = prefabs.resolve(&archetype.prefab, spritesheets)?;
+            if archetype.prefab.role.is_none() {
+                return Err(format!("{} needs a role", archetype.name));
+            }
+        }
+        Ok(archetypes)
+    }
+
+    /// A random kind of enemy, by weight, which suits the game mode (or has the given role)
+    pub fn choose(
+        &self,
+        mode: GameMode,
+        role: Option<EnemyRole>,
+        rng: &mut impl Rng,
+    ) -> Option<&Archetype> {
+        let candidates: Vec<&Archetype> = self
+            .archetypes
+            .iter()
+            .filter(|archetype| match role {
+                Some(role) => archetype.role() == role,
+                None => archetype.suits(mode),
+            })
+            .collect();
+        candidates
+            .choose_weighted(rng, |archetype| archetype.weight)
+            .ok()
+            .copied()
+    }
+}
@@ -0,0 +1,389 @@
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+
+use crate::map::{TileMap, GENERATED_TILE_SIZE};
+use crate::vector::Vector2;
+
+/// The chance each tile of a cave starts out as wall, before being smoothed
+const CAVE_FILL_CHANCE: f64 = 0.45;
+const CAVE_SMOOTHING_PASSES: usize = 5;
+/// A cave tile becomes wall when at least this many of its 8 neighbours are walls, and stays
+/// wall with one fewer
+const CAVE_WALL_NEIGHBOURS: usize = 5;
+const MAX_ROOMS: usize = 10;
+const ROOM_PLACEMENT_ATTEMPTS: usize = 100;
+const MIN_ROOM_SIZE: u32 = 4;
+const MAX_ROOM_SIZE: u32 = 10;
+/// How many tiles of floor corridors have either side of their middle, at least
+const CORRIDOR_HALF_WIDTH: u32 = 1;
+/// How many enemy spawn zones a cave gets
+const CAVE_SPAWN_ZONES: usize = 4;
+
+/// How an arena is laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Generator {
+    /// Rectangular rooms joined by corridors
+    Rooms,
+    /// Organic caves grown by cellular automata
+    Caves,
+}
+
+/// Settings for generating an arena instead of loading a map
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArenaConfig {
+    pub generator: Generator,
+    /// The size of the arena, in tiles
+    pub columns: u32,
+    pub rows: u32,
+    /// Where to save each generated arena as a map, so a layout can be reproduced
+    pub export: Option<PathBuf>,
+}
+
+impl Default for ArenaConfig {
+    fn default() -> ArenaConfig {
+        ArenaConfig {
+            generator: Generator::Caves,
+            columns: 50,
+            rows: 38,
+            export: None,
+        }
+    }
+}
+
+/// A rectangle of tiles
+#[derive(Debug, Clone, Copy)]
+struct Room {
+    column: u32,
+    row: u32,
+    columns: u32,
+    rows: u32,
+}
+
+/// How many tiles of floor something needs on every side of the tile it's centred on, for a
+/// hitbox reaching `reach` pixels from its position to fit
+fn clearance(reach: u32) -> u32 {
+    let beyond_tile = reach.saturating_sub(GENERATED_TILE_SIZE / 2);
+    beyond_tile.div_ceil(GENERATED_TILE_SIZE)
+}
+
+impl Room {
+    fn center(&self) -> (u32, u32) {
+        (self.column + self.columns / 2, self.row + self.rows / 2)
+    }
+
+    /// Whether the rooms overlap or touch
+    fn touches(&self, other: &Room) -> bool {
+        self.column <= other.column + other.columns
+            && other.column <= self.column + self.columns
+            && self.row <= other.row + other.rows
+            && other.row <= self.row + self.rows
+    }
+}
+
+/// Which tiles of the arena are walls, row by row
+struct Grid {
+    columns: u32,
+    rows: u32,
+    walls: Vec<bool>,
+}
+
+impl Grid {
+    fn filled(columns: u32, rows: u32) -> Grid {
+        Grid {
+            columns,
+            rows,
+            walls: vec![true; (columns * rows) as usize],
+        }
+    }
+
+    fn index(&self, column: u32, row: u32) -> usize {
+        (row * self.columns + column) as usize
+    }
+
+    fn is_wall(&self, column: u32, row: u32) -> bool {
+        self.walls[self.index(column, row)]
+    }
+
+    fn set_wall(&mut self, column: u32, row: u32, wall: bool) {
+        let index = self.index(column, row);
+        self.walls[index] = wall;
+    }
+
+    fn is_edge(&self, column: u32, row: u32) -> bool {
+        column == 0 || row == 0 || column == self.columns - 1 || row == self.rows - 1
+    }
+
+    fn wall_neighbours(&self, column: u32, row: u32) -> usize {
+        let mut count = 0;
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                if dx == 0 && dy == 0 {
+                    continue;
+                }
+                let (x, y) = (column as i32 + dx, row as i32 + dy);
+                // Beyond the edge counts as wall, so caves stay closed
+                let outside = x < 0 || y < 0 || x >= self.columns as i32 || y >= self.rows as i32;
+                if outside || self.is_wall(x as u32, y as u32) {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    /// Whether something centred on the tile, needing `clearance` tiles of floor on every side,
+    /// fits there
+    fn fits(&self, column: u32, row: u32, clearance: u32) -> bool {
+        if column < clearance
+            || row < clearance
+            || column + clearance >= self.columns
+            || row + clearance >= self.rows
+        {
+            return false;
+        }
+        (row - clearance..=row + clearance)
+            .all(|y| (column - clearance..=column + clearance).all(|x| !self.is_wall(x, y)))
+    }
+
+    /// The number of steps to each tile something needing `clearance` tiles of floor around it
+    /// can reach from `start`, or None for the rest
+    fn distances_from(&self, start: (u32, u32), clearance: u32) -> Vec<Option<u32>> {
+        let mut distances = vec![None; self.walls.len()];
+        if !self.fits(start.0, start.1, clearance) {
+            return distances;
+        }
+        distances[self.index(start.0, start.1)] = Some(0);
+        let mut frontier = VecDeque::from(vec![start]);
+        while let Some((column, row)) = frontier.pop_front() {
+            let distance = distances[self.index(column, row)].unwrap();
+            let neighbours = [
+                (column.wrapping_sub(1), row),
+                (column + 1, row),
+                (column, row.wrapping_sub(1)),
+                (column, row + 1),
+            ];
+            for &(x, y) in &neighbours {
+                if x >= self.columns || y >= self.rows || !self.fits(x, y, clearance) {
+                    continue;
+                }
+                let index = self.index(x, y);
+                if distances[index].is_none() {
+                    distances[index] = Some(distance + 1);
+                    frontier.push_back((x, y));
+                }
+            }
+        }
+        distances
+    }
+
+    /// Wall off every floor tile which something needing `clearance` tiles of floor around it
+    /// can't reach (any part of) from `start`, guaranteeing everything left is connected to it
+    fn keep_reachable_from(&mut self, start: (u32, u32), clearance: u32) -> Vec<Option<u32>> {
+        let distances = self.distances_from(start, clearance);
+        let mut covered = vec![false; self.walls.len()];
+        for (index, distance) in distances.iter().enumerate() {
+            if distance.is_none() {
+                continue;
+            }
+            let (column, row) = (index as u32 % self.columns, index as u32 / self.columns);
+            for y in row - clearance..=row + clearance {
+                for x in column - clearance..=column + clearance {
+                    covered[self.index(x, y)] = true;
+                }
+            }
+        }
+        for (wall, covered) in self.walls.iter_mut().zip(&covered) {
+            if !covered {
+                *wall = true;
+            }
+        }
+        distances
+    }
+
+    /// The tile nearest to the center where something needing `clearance` tiles of floor around
+    /// it fits, if there's anywhere
+    fn fitting_nearest_center(&self, clearance: u32) -> Option<(u32, u32)> {
+        let center = (self.columns as i32 / 2, self.rows as i32 / 2);
+        (0..self.rows)
+            .flat_map(|row| (0..self.columns).map(move |column| (column, row)))
+            .filter(|&(column, row)| self.fits(column, row, clearance))
+            .min_by_key(|&(column, row)| {
+                (column as i32 - center.0).pow(2) + (row as i32 - center.1).pow(2)
+            })
+    }
+}
+
+/// Grow caves by randomly filling the grid with walls, then repeatedly smoothing them out
+fn caves(columns: u32, rows: u32, rng: &mut impl Rng) -> Grid {
+    let mut grid = Grid::filled(columns, rows);
+    for row in 0..rows {
+        for column in 0..columns {
+            let wall = grid.is_edge(column, row) || rng.gen_bool(CAVE_FILL_CHANCE);
+            grid.set_wall(column, row, wall);
+        }
+    }
+    for _ in 0..CAVE_SMOOTHING_PASSES {
+        let mut smoothed = Grid::filled(columns, rows);
+        for row in 0..rows {
+            for column in 0..columns {
+                let neighbours = grid.wall_neighbours(column, row);
+                let wall = grid.is_edge(column, row)
+                    || neighbours >= CAVE_WALL_NEIGHBOURS
+                    || (grid.is_wall(column, row) && neighbours == CAVE_WALL_NEIGHBOURS - 1);
+                smoothed.set_wall(column, row, wall);
+            }
+        }
+        grid = smoothed;
+    }
+    grid
+}
+
+/// Carve non-overlapping rooms out of solid rock, joining each to the last by a corridor. Rooms
+/// and corridors are wide enough for something needing `clearance` tiles of floor around it.
+fn rooms(columns: u32, rows: u32, clearance: u32, rng: &mut impl Rng) -> (Grid, Vec<Room>) {
+    let mut grid = Grid::filled(columns, rows);
+    let mut rooms: Vec<Room> = Vec::new();
+    let min_size = MIN_ROOM_SIZE.max(clearance * 2 + 1);
+    let max_size = MAX_ROOM_SIZE
+        .min(columns.saturating_sub(2))
+        .min(rows.saturating_sub(2));
+    if max_size < min_size {
+        return (grid, rooms);
+    }
+    let corridor_half_width = CORRIDOR_HALF_WIDTH.max(clearance);
+
+    for _ in 0..ROOM_PLACEMENT_ATTEMPTS {
+        if rooms.len() == MAX_ROOMS {
+            break;
+        }
+        let room_columns = rng.gen_range(min_size..=max_size);
+        let room_rows = rng.gen_range(min_size..=max_size);
+        let room = Room {
+            column: rng.gen_range(1..=columns - room_columns - 1),
+            row: rng.gen_range(1..=rows - room_rows - 1),
+            columns: room_columns,
+            rows: room_rows,
+        };
+        if rooms.iter().any(|other| room.touches(other)) {
+            continue;
+        }
+
+        for row in room.row..room.row + room.rows {
+            for column in room.column..room.column + room.columns {
+                grid.set_wall(column, row, false);
+            }
+        }
+        if let Some(previous) = rooms.last() {
+            // An L-shaped corridor, turning at a random corner
+            let (from, to) = (previous.center(), room.center());
+            let corner = match rng.gen_bool(0.5) {
+                true => (to.0, from.1),
+                false => (from.0, to.1),
+            };
+            for &((x1, y1), (x2, y2)) in &[(from, corner), (corner, to)] {
+                let corridor_rows = y1.min(y2).saturating_sub(corridor_half_width).max(1)
+                    ..=(y1.max(y2) + corridor_half_width).min(rows - 2);
+                let corridor_columns = x1.min(x2).saturating_sub(corridor_half_width).max(1)
+                    ..=(x1.max(x2) + corridor_half_width).min(columns - 2);
+                for row in corridor_rows {
+                    for column in corridor_columns.clone() {
+                        grid.set_wall(column, row, false);
+                    }
+                }
+            }
+        }
+        rooms.push(room);
+    }
+    (grid, rooms)
+}
+
+/// The world rectangle (top left and bottom right corners) of positions in a room of the map
+/// where something needing `clearance` tiles of floor around it fits
+fn zone(map: &TileMap, room: &Room, clearance: u32) -> (Vector2, Vector2) {
+    let top_left = map
+        .tile_rect(room.column + clearance, room.row + clearance)
+        .center();
+    let bottom_right = map
+        .tile_rect(
+            room.column + room.columns - 1 - clearance,
+            room.row + room.rows - 1 - clearance,
+        )
+        .center();
+    (
+        Vector2::new(top_left.x() as f32, top_left.y() as f32),
+        Vector2::new(bottom_right.x() as f32, bottom_right.y() as f32),
+    )
+}
+
+/// Generate an arena from a seed, for hitboxes reaching up to `reach` pixels from their
+/// entity's position. The hero starts near the middle, and every enemy spawn zone can be
+/// reached from there by the largest of them.
+pub fn generate(settings: &ArenaConfig, seed: u64, reach: u32) -> Result<TileMap, String> {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let (columns, rows) = (settings.columns, settings.rows);
+    let clearance = clearance(reach);
+
+    let (mut grid, rooms) = match settings.generator {
+        Generator::Caves => (caves(columns, rows, &mut rng), Vec::new()),
+        Generator::Rooms => rooms(columns, rows, clearance, &mut rng),
+    };
+    let start = match rooms.first() {
+        Some(room) => room.center(),
+        None => grid.fitting_nearest_center(clearance).ok_or(
+            "Generated an arena without room for the hero, try a larger or different arena",
+        )?,
+    };
+    let distances = grid.keep_reachable_from(start, clearance);
+
+    let mut map = TileMap::from_walls(columns, rows, &grid.walls);
+    let start_rect = map.tile_rect(start.0, start.1);
+    map.hero_start = Some(Vector2::new(
+        start_rect.center().x() as f32,
+        start_rect.center().y() as f32,
+    ));
+
+    map.spawn_zones = match settings.generator {
+        // Every room but the hero's
+        Generator::Rooms => rooms
+            .iter()
+            .skip(1)
+            .filter(|room| {
+                let (column, row) = room.center();
+                distances[grid.index(column, row)].is_some()
+            })
+            .map(|room| zone(&map, room, clearance))
+            .collect(),
+        // Tiles picked from the furthest quarter of the cave from the hero
+        Generator::Caves => {
+            let mut reachable: Vec<(usize, u32)> = distances
+                .iter()
+                .enumerate()
+                .filter_map(|(index, distance)| distance.map(|distance| (index, distance)))
+                .collect();
+            reachable.sort_by_key(|&(_, distance)| distance);
+            let furthest = &reachable[reachable.len() * 3 / 4..];
+            furthest
+                .choose_multiple(&mut rng, CAVE_SPAWN_ZONES)
+                .map(|&(index, _)| {
+                    // The block of floor around the tile, so the zone is just its centre
+                    let tile = Room {
+                        column: index as u32 % columns - clearance,
+                        row: index as u32 / columns - clearance,
+                        columns: clearance * 2 + 1,
+                        rows: clearance * 2 + 1,
+                    };
+                    zone(&map, &tile, clearance)
+                })
+                .collect()
+        }
+    };
+    if map.spawn_zones.is_empty() {
+        return Err("Generated an arena without anywhere to spawn enemies".to_string());
+    }
+    Ok(map)
+}
@@ -0,0 +1,116 @@
+use specs::prelude::*;
+
+use crate::components::*;
+use crate::config::{BoundaryPolicy, Config};
+
+pub struct Boundary;
+
+/**
+ * Apply the configured boundary policy to every hero and enemy which has left the arena:
+ * purging it, clamping it to the edge, bouncing it back in or wrapping it around to the other
+ * side. A purged hero is defeated. Entities held against the edge are only counted once, when
+ * they first reach it.
+ */
+impl<'a> System<'a> for Boundary {
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, Config>,
+        ReadStorage<'a, Hero>,
+        ReadStorage<'a, Enemy>,
+        WriteStorage<'a, Position>,
+        WriteStorage<'a, Velocity>,
+        WriteStorage<'a, Health>,
+        WriteStorage<'a, AgainstEdge>,
+        WriteStorage<'a, Telemetry>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            config,
+            heroes,
+            enemies,
+            mut positions,
+            mut velocities,
+            mut healths,
+            mut against_edge,
+            mut telemetries,
+        ): Self::SystemData,
+    ) {
+        let (min, max) = config.world_bounds();
+        let size = max - min;
+
+        let mut applied = Vec::new();
+        for (entity, pos, vel) in (&entities, &mut positions, (&mut velocities).maybe()).join() {
+            let policy = if heroes.contains(entity) {
+                config.hero_boundary
+            } else if enemies.contains(entity) {
+                config.enemy_boundary
+            } else {
+                continue;
+            };
+            let outside_x = pos.0.x < min.x || pos.0.x > max.x;
+            let outside_y = pos.0.y < min.y || pos.0.y > max.y;
+            if !outside_x && !outside_y {
+                // Only leaving the edge lets reaching it again count as another crossing
+                let inside =
+                    pos.0.x > min.x && pos.0.x < max.x && pos.0.y > min.y && pos.0.y < max.y;
+                if inside {
+                    against_edge.remove(entity);
+                }
+                continue;
+            }
+
+            match policy {
+                BoundaryPolicy::Purge if heroes.contains(entity) => {
+                    if let Some(health) = healths.get_mut(entity) {
+                        health.current = 0;
+                    }
+                }
+                BoundaryPolicy::Purge => entities.delete(entity).unwrap(),
+                BoundaryPolicy::Clamp => {
+                    pos.0.x = pos.0.x.clamp(min.x, max.x);
+                    pos.0.y = pos.0.y.clamp(min.y, max.y);
+                    if against_edge.insert(entity, AgainstEdge).unwrap().is_some() {
+                        continue;
+                    }
+                }
+                BoundaryPolicy::Bounce => {
+                    pos.0.x = pos.0.x.clamp(min.x, max.x);
+                    pos.0.y = pos.0.y.clamp(min.y, max.y);
+                    if let Some(vel) = vel {
+                        if outside_x {
+                            vel.0.x = -vel.0.x;
+                        }
+                        if outside_y {
+                            vel.0.y = -vel.0.y;
+                        }
+                    }
+                }
+                BoundaryPolicy::Wrap => {
+                    pos.0.x = min.x + (pos.0.x - min.x).rem_euclid(size.x);
+                    pos.0.y = min.y + (pos.0.y - min.y).rem_euclid(size.y);
+                }
+            }
+            applied.push(policy);
+        }
+
+        if applied.is_empty() {
+            return;
+        }
+        match (&mut telemetries).join().last() {
+            Some(telemetry) => {
+                for policy in applied {
+                    match policy {
+                        BoundaryPolicy::Purge => telemetry.oob_purged += 1,
+                        BoundaryPolicy::Clamp => telemetry.oob_clamped += 1,
+                        BoundaryPolicy::Bounce => telemetry.oob_bounced += 1,
+                        BoundaryPolicy::Wrap => telemetry.oob_wrapped += 1,
+                    }
+                }
+            }
+            None => eprintln!("Telemetry Missing"),
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use sdl2::rect::{Point, Rect};
+use specs::prelude::*;
+
+use crate::components::*;
+use crate::vector::Vector2;
+
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = 4.0;
+/// How much each zoom step scales the view by
+const ZOOM_STEP: f32 = 1.25;
+/// How far each pan step moves the view, in screen pixels
+const PAN_STEP: f32 = 50.0;
+/// The fraction of the way to its target the camera moves each frame while following
+const FOLLOW_SMOOTHING: f32 = 0.1;
+
+/// A change to what the camera is looking at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraControl {
+    Pan(Direction),
+    ZoomIn,
+    ZoomOut,
+    /// Start (or stop) following the hero
+    ToggleFollow,
+}
+
+/// The view of the world shown on screen
+pub struct Camera {
+    /// The world position shown at the center of the screen
+    pub position: Vector2,
+    /// How many screen pixels each world pixel covers
+    pub zoom: f32,
+    /// The entity the camera smoothly keeps in view, if any
+    pub follow: Option<Entity>,
+    /// The size of the screen being rendered to, in pixels
+    pub viewport: (u32, u32),
+}
+
+impl Camera {
+    pub fn new(viewport: (u32, u32)) -> Camera {
+        Camera {
+            position: Vector2::ZERO,
+            zoom: 1.0,
+            follow: None,
+            viewport,
+        }
+    }
+
+    fn screen_center(&self) -> Vector2 {
+        Vector2::new(self.viewport.0 as f32 / 2.0, self.viewport.1 as f32 / 2.0)
+    }
+
+    /// Where a world position appears on screen
+    pub fn to_screen(&self, position: Vector2) -> Point {
+        let screen = (position - self.position) * self.zoom + self.screen_center();
+        Point::new(screen.x.round() as i32, screen.y.round() as i32)
+    }
+
+    /// The world position shown at a point on screen
+    pub fn to_world(&self, x: i32, y: i32) -> Vector2 {
+        (Vector2::new(x as f32, y as f32) - self.screen_center()) / self.zoom + self.position
+    }
+
+    /// The on screen size of a world length
+    pub fn scale(&self, length: u32) -> u32 {
+        ((length as f32 * self.zoom).round() as u32).max(1)
+    }
+
+    /// The on screen rectangle covering a world rectangle
+    pub fn to_screen_rect(&self, rect: Rect) -> Rect {
+        let top_left = Vector2::new(rect.x() as f32, rect.y() as f32);
+        let top_left = self.to_screen(top_left);
+        Rect::new(
+            top_left.x(),
+            top_left.y(),
+            self.scale(rect.width()),
+            self.scale(rect.height()),
+        )
+    }
+
+    /// The on screen rectangle covering a world rectangle of the given size centered on `center`
+    pub fn to_screen_rect_centered(&self, center: Vector2, width: u32, height: u32) -> Rect {
+        Rect::from_center(
+            self.to_screen(center),
+            self.scale(width),
+            self.scale(height),
+        )
+    }
+
+    /// Zoom by a number of steps (negative to zoom out)
+    pub fn zoom_by(&mut self, steps: i32) {
+        self.zoom = (self.zoom * ZOOM_STEP.powi(steps)).clamp(MIN_ZOOM, MAX_ZOOM);
+    }
+
+    /// Apply a control, where `hero` is the entity to follow if asked to
+    pub fn apply(&mut self, control: CameraControl, hero: Option<Entity>) {
+        match control {
+            CameraControl::Pan(direction) => {
+                let offset = match direction {
+                    Direction::Up => Vector2::new(0.0, -1.0),
+                    Direction::Down => Vector2::new(0.0, 1.0),
+                    Direction::Left => Vector2::new(-1.0, 0.0),
+                    Direction::Right => Vector2::new(1.0, 0.0),
+                };
+                // Panning takes over from following
+                self.follow = None;
+                self.position += offset * (PAN_STEP / self.zoom);
+            }
+            CameraControl::ZoomIn => self.zoom_by(1),
+            CameraControl::ZoomOut => self.zoom_by(-1),
+            CameraControl::ToggleFollow => {
+                self.follow = match self.follow {
+                    Some(_) => None,
+                    None => hero,
+                }
+            }
+        }
+    }
+
+    /// Move smoothly towards the followed entity, if it's still around
+    pub fn update(&mut self, positions: &ReadStorage<Position>) {
+        if let Some(entity) = self.follow {
+            match positions.get(entity) {
+                Some(pos) => self.position += (pos.0 - self.position) * FOLLOW_SMOOTHING,
+                None => self.follow = None,
+            }
+        }
+    }
+}
@@ -0,0 +1,72 @@
+/// How many ticks are dispatched each second at normal speed (one per frame)
+pub const TICKS_PER_SECOND: u32 = 20;
+
+/// The speeds the simulation can run at, relative to one tick per frame
+const TIME_SCALES: [f32; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+const NORMAL_TIME_SCALE: usize = 2;
+
+/// A debugging control over the simulation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockControl {
+    /// Stop (or restart) dispatching
+    ToggleFreeze,
+    /// While frozen, dispatch exactly one tick
+    Step,
+    SlowDown,
+    SpeedUp,
+}
+
+/// Decides how many ticks of the simulation to dispatch each frame
+pub struct SimulationClock {
+    frozen: bool,
+    time_scale: usize,
+    /// Single steps requested while frozen, not yet dispatched
+    pending_steps: u32,
+    /// Fractional ticks carried over between frames when running slower than normal
+    accumulated: f32,
+}
+
+impl SimulationClock {
+    pub fn new() -> SimulationClock {
+        SimulationClock {
+            frozen: false,
+            time_scale: NORMAL_TIME_SCALE,
+            pending_steps: 0,
+            accumulated: 0.0,
+        }
+    }
+
+    pub fn apply(&mut self, control: ClockControl) {
+        match control {
+            ClockControl::ToggleFreeze => {
+                self.frozen = !self.frozen;
+                self.pending_steps = 0;
+            }
+            ClockControl::Step if self.frozen => self.pending_steps += 1,
+            ClockControl::Step => {}
+            ClockControl::SlowDown => self.time_scale = self.time_scale.saturating_sub(1),
+            ClockControl::SpeedUp => {
+                self.time_scale = (self.time_scale + 1).min(TIME_SCALES.len() - 1)
+            }
+        }
+    }
+
+    /// How many times to dispatch this frame
+    pub fn ticks_this_frame(&mut self) -> u32 {
+        if self.frozen {
+            return std::mem::take(&mut self.pending_steps);
+        }
+        self.accumulated += TIME_SCALES[self.time_scale];
+        let ticks = self.accumulated.floor();
+        self.accumulated -= ticks;
+        ticks as u32
+    }
+
+    /// The current mode, for display on the HUD
+    pub fn status(&self) -> String {
+        match self.frozen {
+            true => "FROZEN".to_string(),
+            false => format!("Speed: {}x", TIME_SCALES[self.time_scale]),
+        }
+    }
+}
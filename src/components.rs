@@ -1,7 +1,25 @@
 use sdl2::rect::{Point, Rect};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use specs::prelude::*;
 use specs_derive::Component;
 
+use crate::steering::{Target, WeightedBehaviour};
+use crate::vector::Vector2;
+
+/// (De)serialize a rectangle as its x, y, width and height
+mod rect {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(rect: &Rect, serializer: S) -> Result<S::Ok, S::Error> {
+        (rect.x(), rect.y(), rect.width(), rect.height()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Rect, D::Error> {
+        let (x, y, width, height) = Deserialize::deserialize(deserializer)?;
+        Ok(Rect::new(x, y, width, height))
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Direction {
     Up,
@@ -10,6 +28,23 @@ pub enum Direction {
     Right,
 }
 
+impl Direction {
+    /// The direction closest to the given heading, used to pick a sprite row for any angle
+    pub fn from_heading(heading: Vector2) -> Direction {
+        if heading.x.abs() >= heading.y.abs() {
+            if heading.x < 0.0 {
+                Direction::Left
+            } else {
+                Direction::Right
+            }
+        } else if heading.y < 0.0 {
+            Direction::Up
+        } else {
+            Direction::Down
+        }
+    }
+}
+
 #[derive(Component, Debug, Default)]
 #[storage(NullStorage)]
 pub struct KeyboardControlled;
@@ -26,38 +61,164 @@ pub struct Enemy;
 #[storage(NullStorage)]
 pub struct Hero;
 
-/// The current position of a given entity
-#[derive(Component, Debug, Clone)]
+/// Picked out with the mouse, and highlighted
+#[derive(Component, Debug, Default)]
+#[storage(NullStorage)]
+pub struct Selected;
+
+/// Clamped to the edge of the arena, and still pushing against it
+#[derive(Component, Debug, Default)]
+#[storage(NullStorage)]
+pub struct AgainstEdge;
+
+/// The current (sub-pixel) position of a given entity
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+#[storage(VecStorage)]
+pub struct Position(pub Vector2);
+
+impl Position {
+    /// The nearest whole pixel to this position
+    pub fn to_point(&self) -> Point {
+        Point::new(self.0.x.round() as i32, self.0.y.round() as i32)
+    }
+}
+
+/// The current velocity of a given entity, in pixels per tick
+#[derive(Component, Debug, Default, Clone, Serialize, Deserialize)]
+#[storage(VecStorage)]
+pub struct Velocity(pub Vector2);
+
+/// Steering behaviours combined to move an AI controlled entity
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+#[storage(VecStorage)]
+pub struct Steering {
+    pub behaviours: Vec<WeightedBehaviour>,
+    /// The fastest this entity will move, in pixels per tick
+    pub max_speed: f32,
+    /// The largest change in velocity allowed per tick
+    pub max_force: f32,
+    /// Current angle around the wander circle, persisted between ticks
+    pub wander_angle: f32,
+    /// The entity each kind of target resolved to on the last tick, for debugging
+    #[serde(skip)]
+    pub targets: Vec<(Target, Entity)>,
+}
+
+impl Steering {
+    pub fn new(behaviours: Vec<WeightedBehaviour>, max_speed: f32, max_force: f32) -> Steering {
+        Steering {
+            behaviours,
+            max_speed,
+            max_force,
+            wander_angle: 0.0,
+            targets: Vec::new(),
+        }
+    }
+}
+
+/// How much more damage an entity can take before it is defeated
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+#[storage(HashMapStorage)]
+pub struct Health {
+    pub current: u32,
+    pub max: u32,
+}
+
+/// How much health an enemy takes from the hero on contact
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 #[storage(VecStorage)]
-pub struct Position(pub Point);
+pub struct Damage(pub u32);
 
-/// The current speed and direction of a given entity
-#[derive(Component, Debug)]
+/// The region (relative to its position, the centre of its sprite) over which an entity touches
+/// others
+#[derive(Component, Debug, Clone, Copy, Serialize, Deserialize)]
 #[storage(VecStorage)]
-pub struct Velocity {
-    pub speed: i32,
-    pub direction: Direction,
+pub struct Hitbox(#[serde(with = "rect")] pub Rect);
+
+/// Points scored for catching an enemy
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+#[storage(VecStorage)]
+pub struct Score(pub u32);
+
+/// Temporary immunity to damage, granted after taking a hit
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+#[storage(HashMapStorage)]
+pub struct Invulnerable {
+    pub ticks_remaining: u32,
 }
 
-/// The current speed and direction of a given entity
-#[derive(Component, Debug)]
+/// How an enemy reacts to the hero
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[storage(VecStorage)]
+pub enum EnemyRole {
+    /// Runs from the hero and is caught on contact
+    Prey,
+    /// Hunts the hero and tags it on contact
+    Chaser,
+}
+
+/// How far away an entity can spot the hero, and whether obstacles block its view
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+#[storage(VecStorage)]
+pub struct Perception {
+    pub radius: f32,
+    pub line_of_sight: bool,
+}
+
+/// A static circular obstacle which steering entities avoid
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+#[storage(VecStorage)]
+pub struct Obstacle {
+    pub radius: f32,
+}
+
+/// Running counters of what has happened in the game so far
+#[derive(Component, Debug, Default, Clone, Serialize, Deserialize)]
 #[storage(HashMapStorage)]
 pub struct Telemetry {
+    /// Prey the hero has caught
     pub enemy_collisions: u32,
-    pub enemy_oob: u32,
+    /// Times an enemy has damaged the hero
+    pub hero_tagged: u32,
     pub enemy_spawned: u32,
+    /// Times a hero or enemy left the arena, by the boundary policy applied
+    pub oob_purged: u32,
+    pub oob_clamped: u32,
+    pub oob_bounced: u32,
+    pub oob_wrapped: u32,
+    /// Points scored for catching prey
+    pub score: u32,
+}
+
+impl Telemetry {
+    /// A multi-line, human readable summary of the counters
+    pub fn summary(&self) -> String {
+        format!(
+            "Score: {}\nSpawned: {}\nOOB: {} purged, {} clamped, {} bounced, {} wrapped\nCaught: {}\nTagged: {}",
+            self.score,
+            self.enemy_spawned,
+            self.oob_purged,
+            self.oob_clamped,
+            self.oob_bounced,
+            self.oob_wrapped,
+            self.enemy_collisions,
+            self.hero_tagged
+        )
+    }
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 #[storage(VecStorage)]
 pub struct Sprite {
     /// The specific spritesheet to render from
     pub spritesheet: usize,
     /// The current region of the spritesheet to be rendered
+    #[serde(with = "rect")]
     pub region: Rect,
 }
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 #[storage(VecStorage)]
 pub struct MovementAnimation {
     // The current frame in the animation of the direction this entity is moving in
@@ -0,0 +1,153 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::arena::ArenaConfig;
+use crate::input::Action;
+use crate::vector::Vector2;
+
+/// Where settings are read from when no path is given on the command line
+pub const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+/// The rules deciding who chases whom
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GameMode {
+    /// The hero chases enemies, which flee once they spot it
+    Hunt,
+    /// Enemies chase the hero, which loses once it is tagged
+    Tag,
+    /// Some enemies flee while others chase
+    Mixed,
+}
+
+/// What happens to an entity which leaves the arena
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BoundaryPolicy {
+    /// Remove it from the game (a purged hero is defeated)
+    Purge,
+    /// Stop it at the edge
+    Clamp,
+    /// Stop it at the edge and reflect its velocity back into the arena
+    Bounce,
+    /// Move it to the opposite edge
+    Wrap,
+}
+
+/// Who moves the hero
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HeroControl {
+    AI,
+    Keyboard,
+}
+
+/// Startup settings, read from a TOML file. Any setting missing from the file uses its default.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// A Tiled JSON map to play on, which sets the size of the arena
+    pub map: Option<PathBuf>,
+    /// Settings for generating a fresh arena each game when there's no map
+    pub arena: Option<ArenaConfig>,
+    /// The size of the arena, centered on (0, 0), when there's no map or generated arena
+    pub world_width: u32,
+    pub world_height: u32,
+    /// The initial size of the (resizable) window
+    pub window_width: u32,
+    pub window_height: u32,
+    /// A fixed resolution to render at, scaled to fit the window. Without one, resizing the
+    /// window shows more (or less) of the world instead.
+    pub logical_resolution: Option<(u32, u32)>,
+    /// What happens to enemies which leave the arena
+    pub enemy_boundary: BoundaryPolicy,
+    /// What happens to the hero if it leaves the arena
+    pub hero_boundary: BoundaryPolicy,
+    /// The most enemies the spawner keeps in play at once
+    pub max_enemies: usize,
+    /// A TOML file of waves deciding when, where and what enemies spawn. Without one, an enemy
+    /// spawns every tick while there's room.
+    pub spawn_rules: Option<PathBuf>,
+    /// A TOML file of named prefabs: the components entities are made from. It must have a
+    /// "hero" prefab, and an "enemy" prefab which enemy archetypes are based on.
+    pub prefabs: PathBuf,
+    /// A TOML file of the kinds of enemy which can spawn. Without one, there are enemies which
+    /// flee and red enemies which chase.
+    pub archetypes: Option<PathBuf>,
+    /// How many seconds of the game to keep a snapshot of every tick of, for rewinding while
+    /// paused. Nothing is kept when this is 0.
+    pub rewind_seconds: f32,
+    /// Seed for the game's random numbers, so games can be replayed. Unseeded games are random.
+    pub seed: Option<u64>,
+    /// Enemies flock together (and flee the hero) instead of wandering independently
+    pub flocking: bool,
+    pub mode: GameMode,
+    /// How far away enemies can spot the hero
+    pub perception_radius: f32,
+    /// Whether enemies need a clear line of sight to spot the hero
+    pub line_of_sight: bool,
+    /// How many hits the hero can take before the game is over
+    pub hero_health: u32,
+    /// Whether chasers in tag games only take health, rather than ending the game on contact
+    pub tag_uses_health: bool,
+    /// Who moves the hero at the start of a game (this can be swapped while playing)
+    pub hero_control: HeroControl,
+    /// Keys (by SDL key name) for any actions which shouldn't use their default keys
+    pub key_bindings: HashMap<Action, Vec<String>>,
+    /// Game controller buttons (by SDL button name) for any actions which shouldn't use their
+    /// default buttons
+    pub pad_bindings: HashMap<Action, Vec<String>>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            map: None,
+            arena: None,
+            world_width: 800,
+            world_height: 600,
+            window_width: 800,
+            window_height: 600,
+            logical_resolution: None,
+            enemy_boundary: BoundaryPolicy::Purge,
+            hero_boundary: BoundaryPolicy::Clamp,
+            max_enemies: 50,
+            spawn_rules: None,
+            prefabs: PathBuf::from("assets/prefabs.toml"),
+            archetypes: None,
+            rewind_seconds: 10.0,
+            seed: None,
+            flocking: false,
+            mode: GameMode::Hunt,
+            perception_radius: 150.0,
+            line_of_sight: true,
+            hero_health: 3,
+            tag_uses_health: false,
+            hero_control: HeroControl::AI,
+            key_bindings: HashMap::new(),
+            pad_bindings: HashMap::new(),
+        }
+    }
+}
+
+impl Config {
+    /// The top left and bottom right corners of the arena
+    pub fn world_bounds(&self) -> (Vector2, Vector2) {
+        let half_size = Vector2::new(
+            self.world_width as f32 / 2.0,
+            self.world_height as f32 / 2.0,
+        );
+        (-half_size, half_size)
+    }
+
+    /// Load the config at `path`, falling back to the defaults if there is no such file
+    pub fn load(path: &Path) -> Result<Config, String> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
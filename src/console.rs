@@ -0,0 +1,328 @@
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use specs::prelude::*;
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+
+use crate::components::*;
+use crate::config::Config;
+use crate::enemy_spawner;
+use crate::map::TileMap;
+use crate::prefab::{Prefab, Prefabs};
+use crate::snapshot::Snapshot;
+use crate::spawn_rules::SpawnArea;
+use crate::state::Transition;
+use crate::vector::Vector2;
+use crate::GameRng;
+
+/// How many lines of output the console keeps
+const MAX_LOG_LINES: usize = 12;
+/// Spawning "enemy" chooses an enemy archetype suited to the game mode, rather than a prefab
+const ENEMY: &str = "enemy";
+
+const HELP: &str = "Commands:
+  spawn <enemy|prefab name> [count] [at <x> <y>]
+  set <max_enemies|perception_radius|flocking|line_of_sight|hero_health|tag_uses_health> <value>
+  seed <number>
+  reset
+  kill all
+  kill <entity id>
+  telemetry dump <path.csv>
+  save <path.json>
+  load <path.json>";
+
+/// A command typed into the console
+#[derive(Debug, Clone, PartialEq)]
+enum Command {
+    /// Spawn enemies suiting the game mode, or entities from a named prefab
+    Spawn {
+        prefab: String,
+        count: u32,
+        at: Option<Vector2>,
+    },
+    Set {
+        name: String,
+        value: String,
+    },
+    Seed(u64),
+    Reset,
+    KillAll,
+    Kill(u32),
+    TelemetryDump(String),
+    Save(String),
+    Load(String),
+    Help,
+}
+
+fn parse_number<T: std::str::FromStr>(word: Option<&str>, what: &str) -> Result<T, String> {
+    let word = word.ok_or(format!("Expected {}", what))?;
+    word.parse()
+        .map_err(|_| format!("Expected {}, not {:?}", what, word))
+}
+
+impl Command {
+    fn parse(line: &str) -> Result<Command, String> {
+        let mut words = line.split_whitespace().peekable();
+        let command = match words.next() {
+            Some("spawn") => {
+                let prefab = words
+                    .next()
+                    .ok_or("Expected \"enemy\" or a prefab name")?
+                    .to_string();
+                let count = match words.peek() {
+                    Some(&word) if word != "at" => parse_number(words.next(), "a count")?,
+                    _ => 1,
+                };
+                let at = match words.next() {
+                    Some("at") => Some(Vector2::new(
+                        parse_number(words.next(), "an x coordinate")?,
+                        parse_number(words.next(), "a y coordinate")?,
+                    )),
+                    Some(word) => return Err(format!("Expected \"at\", not {:?}", word)),
+                    None => None,
+                };
+                Command::Spawn { prefab, count, at }
+            }
+            Some("set") => {
+                let name = words.next().ok_or("Expected a setting name")?.to_string();
+                let value = words.next().ok_or("Expected a value")?.to_string();
+                Command::Set { name, value }
+            }
+            Some("seed") => Command::Seed(parse_number(words.next(), "a seed")?),
+            Some("reset") => Command::Reset,
+            Some("kill") => match words.next() {
+                Some("all") => Command::KillAll,
+                word => Command::Kill(parse_number(word, "\"all\" or an entity id")?),
+            },
+            Some("telemetry") => match (words.next(), words.next()) {
+                (Some("dump"), Some(path)) => Command::TelemetryDump(path.to_string()),
+                _ => return Err("Expected: telemetry dump <path.csv>".to_string()),
+            },
+            Some("save") => Command::Save(words.next().ok_or("Expected a path")?.to_string()),
+            Some("load") => Command::Load(words.next().ok_or("Expected a path")?.to_string()),
+            Some("help") => Command::Help,
+            Some(word) => return Err(format!("Unknown command {:?}, try \"help\"", word)),
+            None => return Err("Expected a command".to_string()),
+        };
+        match words.next() {
+            Some(word) => Err(format!("Unexpected {:?}", word)),
+            None => Ok(command),
+        }
+    }
+
+    /// Carry out the command, returning a message for the player and what should happen next
+    fn run(self, world: &mut World, settings: &mut Config) -> Result<(String, Transition), String> {
+        let message = match self {
+            Command::Spawn { prefab, count, at } => {
+                // Check the prefab exists before spawning anything
+                if prefab != ENEMY {
+                    world.read_resource::<Prefabs>().get(&prefab)?;
+                }
+                for _ in 0..count {
+                    let position = match at {
+                        Some(position) => position,
+                        None => enemy_spawner::spawn_position(
+                            &SpawnArea::Zones,
+                            &world.read_resource::<TileMap>(),
+                            world.read_resource::<Config>().world_bounds(),
+                            None,
+                            0.0,
+                            &mut world.write_resource::<GameRng>().0,
+                        ),
+                    };
+                    if prefab == ENEMY {
+                        enemy_spawner::spawn_enemy(world, position)
+                            .ok_or("No kind of enemy suits the game mode")?;
+                    } else {
+                        let entity = world
+                            .read_resource::<Prefabs>()
+                            .get(&prefab)?
+                            .merged(&Prefab::at(position));
+                        entity.instantiate(world);
+                    }
+                }
+                format!("Spawned {} {}", count, prefab)
+            }
+            Command::Set { name, value } => {
+                set(settings, &name, &value)?;
+                // Systems read the world's copy of the settings
+                set(&mut world.write_resource::<Config>(), &name, &value)?;
+                format!("Set {} to {}", name, value)
+            }
+            Command::Seed(seed) => {
+                settings.seed = Some(seed);
+                world.write_resource::<Config>().seed = Some(seed);
+                *world.write_resource::<GameRng>() = GameRng::new(Some(seed));
+                format!("Seeded with {}, reset to replay from the start", seed)
+            }
+            Command::Reset => return Ok(("Reset".to_string(), Transition::NewGame)),
+            Command::KillAll => {
+                let (entities, enemies): (Entities, ReadStorage<Enemy>) = world.system_data();
+                let mut killed = 0;
+                for (entity, _) in (&entities, &enemies).join() {
+                    entities.delete(entity).map_err(|e| e.to_string())?;
+                    killed += 1;
+                }
+                format!("Killed {} enemies", killed)
+            }
+            Command::Kill(id) => {
+                // Look among the living, as a dead id's entity can't even be asked if it's alive
+                let entity = (&world.entities())
+                    .join()
+                    .find(|entity| entity.id() == id)
+                    .ok_or(format!("No entity {}", id))?;
+                world.delete_entity(entity).map_err(|e| e.to_string())?;
+                format!("Killed entity {}", id)
+            }
+            Command::TelemetryDump(path) => {
+                let telemetries = world.read_storage::<Telemetry>();
+                let telemetry = (&telemetries).join().last().ok_or("Telemetry Missing")?;
+                let csv = format!(
+                    "spawned,oob_purged,oob_clamped,oob_bounced,oob_wrapped,caught,tagged,score\n\
+                     {},{},{},{},{},{},{},{}\n",
+                    telemetry.enemy_spawned,
+                    telemetry.oob_purged,
+                    telemetry.oob_clamped,
+                    telemetry.oob_bounced,
+                    telemetry.oob_wrapped,
+                    telemetry.enemy_collisions,
+                    telemetry.hero_tagged,
+                    telemetry.score
+                );
+                fs::write(&path, csv).map_err(|e| format!("{}: {}", path, e))?;
+                format!("Wrote telemetry to {}", path)
+            }
+            Command::Save(path) => {
+                Snapshot::save(world, Path::new(&path))?;
+                format!("Saved the world to {}", path)
+            }
+            Command::Load(path) => {
+                Snapshot::load(world, Path::new(&path))?;
+                let message = format!("Loaded {}", path);
+                return Ok((message, Transition::Loaded));
+            }
+            Command::Help => HELP.to_string(),
+        };
+        Ok((message, Transition::Stay))
+    }
+}
+
+/// Change a setting by name, parsing the value for its type
+fn set(settings: &mut Config, name: &str, value: &str) -> Result<(), String> {
+    let value = Some(value);
+    match name {
+        "max_enemies" => settings.max_enemies = parse_number(value, "a number of enemies")?,
+        "perception_radius" => settings.perception_radius = parse_number(value, "a radius")?,
+        "flocking" => settings.flocking = parse_number(value, "true or false")?,
+        "line_of_sight" => settings.line_of_sight = parse_number(value, "true or false")?,
+        "hero_health" => settings.hero_health = parse_number(value, "an amount of health")?,
+        "tag_uses_health" => settings.tag_uses_health = parse_number(value, "true or false")?,
+        _ => return Err(format!("Unknown setting {:?}", name)),
+    }
+    Ok(())
+}
+
+/// A drop-down text console for manipulating a running game
+#[derive(Default)]
+pub struct Console {
+    open: bool,
+    /// The command being typed
+    input: String,
+    /// Previously entered commands, oldest first
+    history: Vec<String>,
+    /// The index into the history of the command being recalled, if any
+    recalled: Option<usize>,
+    log: VecDeque<String>,
+}
+
+impl Console {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+
+    /// Handle typing into the console, returning the command entered (if one was)
+    pub fn handle_event(&mut self, event: &Event) -> Option<String> {
+        match event {
+            Event::TextInput { text, .. } => self.input += text,
+            Event::KeyDown {
+                keycode: Some(keycode),
+                ..
+            } => match *keycode {
+                Keycode::Escape => self.open = false,
+                Keycode::Backspace => {
+                    self.input.pop();
+                }
+                Keycode::Up => self.recall(
+                    self.recalled
+                        .map_or(self.history.len(), |i| i)
+                        .checked_sub(1),
+                ),
+                Keycode::Down => self.recall(self.recalled.map(|i| i + 1)),
+                Keycode::Return | Keycode::KpEnter if !self.input.trim().is_empty() => {
+                    let line = std::mem::take(&mut self.input);
+                    self.history.push(line.clone());
+                    self.recalled = None;
+                    return Some(line);
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        None
+    }
+
+    /// Show a command from the history, or a blank line past its end
+    fn recall(&mut self, index: Option<usize>) {
+        match index.and_then(|i| self.history.get(i).map(|line| (i, line))) {
+            Some((i, line)) => {
+                self.input = line.clone();
+                self.recalled = Some(i);
+            }
+            None if index.is_some() => {
+                self.input.clear();
+                self.recalled = None;
+            }
+            // Already at the oldest command
+            None => {}
+        }
+    }
+
+    /// Run an entered command, logging the result
+    pub fn execute(&mut self, line: &str, world: &mut World, settings: &mut Config) -> Transition {
+        self.print(format!("> {}", line));
+        match Command::parse(line).and_then(|command| command.run(world, settings)) {
+            Ok((message, transition)) => {
+                self.print(message);
+                transition
+            }
+            Err(e) => {
+                self.print(format!("Error: {}", e));
+                Transition::Stay
+            }
+        }
+    }
+
+    fn print(&mut self, message: String) {
+        for line in message.lines() {
+            self.log.push_back(line.to_string());
+        }
+        while self.log.len() > MAX_LOG_LINES {
+            self.log.pop_front();
+        }
+    }
+
+    /// The console's output and input line, if it's open
+    pub fn text(&self) -> Option<String> {
+        if !self.open {
+            return None;
+        }
+        let mut text: String = self.log.iter().map(|line| format!("{}\n", line)).collect();
+        text += &format!("> {}_", self.input);
+        Some(text)
+    }
+}
@@ -1,48 +1,119 @@
 use specs::prelude::*;
 
 use crate::components::*;
+use crate::config::{Config, GameMode};
 
 pub struct EnemyColliderPurger;
 
-use super::sprite;
 use sdl2::rect::Rect;
 
+/// How long the hero is immune to further damage after being hit
+const INVULNERABILITY_TICKS: u32 = 40;
+
+/// The rectangle an entity collides with others over, offset from its position
+pub fn hitbox_rect(pos: &Position, hitbox: &Hitbox) -> Rect {
+    let point = pos.to_point();
+    Rect::new(
+        point.x + hitbox.0.x(),
+        point.y + hitbox.0.y(),
+        hitbox.0.width(),
+        hitbox.0.height(),
+    )
+}
+
 /**
- * Purge all enemies who collide with a hero
+ * Purge all enemies who collide with a hero. Harmless enemies are caught (scoring points) once
+ * they run out of health, briefly becoming invulnerable after each catch they survive. Damaging
+ * ones hurt the hero (unless it is still invulnerable from an earlier hit), and in tag games
+ * defeat it outright unless the settings say tagging only takes health.
  */
 impl<'a> System<'a> for EnemyColliderPurger {
     type SystemData = (
         Entities<'a>,
+        ReadExpect<'a, Config>,
         ReadStorage<'a, Position>,
         ReadStorage<'a, Hero>,
         ReadStorage<'a, Enemy>,
+        ReadStorage<'a, Hitbox>,
+        ReadStorage<'a, Damage>,
+        ReadStorage<'a, Score>,
+        WriteStorage<'a, Health>,
+        WriteStorage<'a, Invulnerable>,
         WriteStorage<'a, Telemetry>,
     );
 
-    fn run(&mut self, (entities, positions, heroes, enemies, mut telemetries): Self::SystemData) {
-        for (hero_pos, _) in (&positions, &heroes).join() {
-            let hero_rect = Rect::new(
-                hero_pos.0.x,
-                hero_pos.0.y,
-                sprite::HERO_FRAME_WIDTH,
-                sprite::HERO_FRAME_HEIGHT,
-            );
-
-            for (enemy_entity, enemy_pos, _) in (&entities, &positions, &enemies).join() {
-                let enemy_rect = Rect::new(
-                    enemy_pos.0.x,
-                    enemy_pos.0.y,
-                    sprite::ENEMY_FRAME_WIDTH,
-                    sprite::ENEMY_FRAME_HEIGHT,
-                );
-
-                if hero_rect.intersection(enemy_rect).is_some() {
-                    entities.delete(enemy_entity).unwrap();
-                    match (&mut telemetries).join().last() {
-                        Some(telemetry) => telemetry.enemy_collisions += 1,
-                        None => eprintln!("Telemetry Missing"),
+    fn run(
+        &mut self,
+        (
+            entities,
+            config,
+            positions,
+            heroes,
+            enemies,
+            hitboxes,
+            damages,
+            scores,
+            mut healths,
+            mut invulnerables,
+            mut telemetries,
+        ): Self::SystemData,
+    ) {
+        let tagging_defeats = config.mode == GameMode::Tag && !config.tag_uses_health;
+        for (hero_entity, hero_pos, hero_hitbox, _) in
+            (&entities, &positions, &hitboxes, &heroes).join()
+        {
+            let hero_rect = hitbox_rect(hero_pos, hero_hitbox);
+
+            for (enemy_entity, enemy_pos, hitbox, _) in
+                (&entities, &positions, &hitboxes, &enemies).join()
+            {
+                let enemy_rect = hitbox_rect(enemy_pos, hitbox);
+
+                if hero_rect.intersection(enemy_rect).is_none() {
+                    continue;
+                }
+
+                match damages.get(enemy_entity) {
+                    None if invulnerables.contains(enemy_entity) => continue,
+                    None => {
+                        if let Some(health) = healths.get_mut(enemy_entity) {
+                            health.current = health.current.saturating_sub(1);
+                            if health.current > 0 {
+                                let invulnerable = Invulnerable {
+                                    ticks_remaining: INVULNERABILITY_TICKS,
+                                };
+                                invulnerables.insert(enemy_entity, invulnerable).unwrap();
+                                continue;
+                            }
+                        }
+                        match (&mut telemetries).join().last() {
+                            Some(telemetry) => {
+                                telemetry.enemy_collisions += 1;
+                                telemetry.score +=
+                                    scores.get(enemy_entity).map_or(0, |score| score.0);
+                            }
+                            None => eprintln!("Telemetry Missing"),
+                        }
+                    }
+                    Some(_) if invulnerables.contains(hero_entity) => continue,
+                    Some(damage) => {
+                        if let Some(health) = healths.get_mut(hero_entity) {
+                            health.current = match tagging_defeats {
+                                true => 0,
+                                false => health.current.saturating_sub(damage.0),
+                            };
+                        }
+                        let invulnerable = Invulnerable {
+                            ticks_remaining: INVULNERABILITY_TICKS,
+                        };
+                        invulnerables.insert(hero_entity, invulnerable).unwrap();
+                        match (&mut telemetries).join().last() {
+                            Some(telemetry) => telemetry.hero_tagged += 1,
+                            None => eprintln!("Telemetry Missing"),
+                        }
                     }
                 }
+                entities.delete(enemy_entity).unwrap();
             }
         }
     }
@@ -3,45 +3,185 @@ use specs::prelude::*;
 use crate::components::*;
 use rand::prelude::*;
 
-use super::sprite;
-use sdl2::rect::Point;
+use super::archetype::Archetypes;
+use super::config::Config;
+use super::map::TileMap;
+use super::prefab::Prefab;
+use super::spawn_rules::{SpawnArea, SpawnRules, WaveProgress};
+use super::GameRng;
+use crate::vector::Vector2;
 
-const MAX_ENEMIES: usize = 50;
+/// How many random positions to try before settling for one inside a solid tile (or too close
+/// to the hero)
+const MAX_SPAWN_ATTEMPTS: usize = 10;
+/// How far inside the arena enemies spawned at its edges appear
+const EDGE_INSET: f32 = 1.0;
+/// How far to step in from the edge of the arena at a time, looking for an open tile
+const EDGE_STEP: f32 = 8.0;
 
 pub struct EnemySpawner;
 
+/// A random position just inside a random edge of the arena, moved further in until it's clear
+/// of solid tiles
+fn edge_position(map: &TileMap, (min, max): (Vector2, Vector2), rng: &mut impl Rng) -> Vector2 {
+    let (min, max) = (
+        min + Vector2::new(EDGE_INSET, EDGE_INSET),
+        max - Vector2::new(EDGE_INSET, EDGE_INSET),
+    );
+    let x = rng.gen_range(min.x..=max.x);
+    let y = rng.gen_range(min.y..=max.y);
+    let (mut position, inward) = match rng.gen_range(0..4) {
+        0 => (Vector2::new(x, min.y), Vector2::new(0.0, 1.0)),
+        1 => (Vector2::new(x, max.y), Vector2::new(0.0, -1.0)),
+        2 => (Vector2::new(min.x, y), Vector2::new(1.0, 0.0)),
+        _ => (Vector2::new(max.x, y), Vector2::new(-1.0, 0.0)),
+    };
+    let center = (min + max) / 2.0;
+    while map.collides(position) && (position - center).dot(inward) < 0.0 {
+        position += inward * EDGE_STEP;
+    }
+    position
+}
+
+/// A random position in the spawn area, avoiding solid tiles and staying at least `distance`
+/// from `away_from` (e.g. the hero) where possible
+pub fn spawn_position(
+    area: &SpawnArea,
+    map: &TileMap,
+    bounds: (Vector2, Vector2),
+    away_from: Option<Vector2>,
+    distance: f32,
+    rng: &mut impl Rng,
+) -> Vector2 {
+    let mut position = Vector2::ZERO;
+    for _ in 0..MAX_SPAWN_ATTEMPTS {
+        position = match area {
+            SpawnArea::Zones => {
+                let (min, max) = match map.spawn_zones.choose(rng) {
+                    Some(&zone) => zone,
+                    None => (Vector2::new(-200.0, -200.0), Vector2::new(200.0, 200.0)),
+                };
+                Vector2::new(rng.gen_range(min.x..=max.x), rng.gen_range(min.y..=max.y))
+            }
+            SpawnArea::Edges => edge_position(map, bounds, rng),
+            SpawnArea::Points(points) => match points.choose(rng) {
+                Some(&(x, y)) => Vector2::new(x, y),
+                None => Vector2::ZERO,
+            },
+        };
+        let too_close =
+            away_from.is_some_and(|away_from| (position - away_from).length() < distance);
+        if !map.collides(position) && !too_close {
+            break;
+        }
+    }
+    position
+}
+
+/// Spawn an enemy suited to the game mode outside of the dispatcher, e.g. by hand, counting it
+/// in the telemetry. There may be no kind of enemy suited to the mode.
+pub fn spawn_enemy(world: &mut World, position: Vector2) -> Option<Entity> {
+    let mode = world.read_resource::<Config>().mode;
+    let prefab = world
+        .read_resource::<Archetypes>()
+        .choose(mode, None, &mut world.write_resource::<GameRng>().0)?
+        .prefab
+        .merged(&Prefab::at(position));
+    let enemy = prefab.instantiate(world);
+    match (&mut world.write_storage::<Telemetry>()).join().last() {
+        Some(telemetry) => telemetry.enemy_spawned += 1,
+        None => eprintln!("Telemetry Missing"),
+    }
+    Some(enemy)
+}
+
+/**
+ * Spawn enemies following the spawn rules: working through the waves in order, waiting out each
+ * wave's cooldown then spawning its enemies at its (changing) rate, while there's room for them.
+ * Each enemy's kind is chosen by weight from the archetypes suiting the wave or game mode.
+ */
 impl<'a> System<'a> for EnemySpawner {
     type SystemData = (
         Entities<'a>,
         Read<'a, LazyUpdate>,
+        ReadExpect<'a, Config>,
+        WriteExpect<'a, GameRng>,
+        ReadExpect<'a, Archetypes>,
+        Read<'a, TileMap>,
+        Read<'a, SpawnRules>,
+        Write<'a, WaveProgress>,
+        ReadStorage<'a, Hero>,
+        ReadStorage<'a, Position>,
         ReadStorage<'a, Enemy>,
         WriteStorage<'a, Telemetry>,
     );
-    fn run(&mut self, (entities, lazy, enemies, mut telemetries): Self::SystemData) {
+    fn run(
+        &mut self,
+        (
+            entities,
+            lazy,
+            config,
+            mut rng,
+            archetypes,
+            map,
+            rules,
+            mut progress,
+            heroes,
+            positions,
+            enemies,
+            mut telemetries,
+        ): Self::SystemData,
+    ) {
+        if progress.wave >= rules.waves.len() {
+            if !rules.repeat || rules.waves.is_empty() {
+                return;
+            }
+            progress.wave = 0;
+        }
+        let wave = &rules.waves[progress.wave];
+        if !progress.cooldown_started {
+            progress.cooldown_started = true;
+            progress.wait = wave.cooldown;
+        }
+        if progress.wait > 0 {
+            progress.wait -= 1;
+            return;
+        }
+        if wave.count == Some(progress.spawned) {
+            *progress = WaveProgress {
+                wave: progress.wave + 1,
+                ..WaveProgress::default()
+            };
+            return;
+        }
+
         let enemy_count = enemies.join().count();
-        if enemy_count >= MAX_ENEMIES {
+        if enemy_count >= config.max_enemies {
             return;
         }
 
-        let position = Point::new(
-            thread_rng().gen_range(-200..200),
-            thread_rng().gen_range(-200..200),
+        let hero = (&heroes, &positions).join().map(|(_, pos)| pos.0).next();
+        let position = spawn_position(
+            &wave.area,
+            &map,
+            config.world_bounds(),
+            hero,
+            rules.min_hero_distance,
+            &mut rng.0,
         );
-        let enemy_animation = sprite::enemy_animation();
-        lazy.create_entity(&entities)
-            .with(AIControlled)
-            .with(Enemy)
-            .with(Position(position))
-            .with(Velocity {
-                speed: 0,
-                direction: Direction::Right,
-            })
-            .with(enemy_animation.right_frames[0].clone())
-            .with(enemy_animation)
-            .build();
+        let archetype = match archetypes.choose(config.mode, wave.role, &mut rng.0) {
+            Some(archetype) => archetype,
+            None => return,
+        };
+        archetype
+            .prefab
+            .merged(&Prefab::at(position))
+            .instantiate_lazy(&lazy, &entities, &config);
         match (&mut telemetries).join().last() {
             Some(telemetry) => telemetry.enemy_spawned += 1,
             None => eprintln!("Telemetry Missing"),
         }
+        progress.wait = wave.interval_after(progress.spawned).saturating_sub(1);
+        progress.spawned += 1;
     }
 }
@@ -0,0 +1,46 @@
+use sdl2::controller::GameController;
+use sdl2::event::Event;
+use sdl2::GameControllerSubsystem;
+use std::collections::HashMap;
+
+use crate::input::InputState;
+
+/// The game controllers currently plugged in. SDL reports a connection event for each
+/// controller already plugged in at startup, so those are opened the same way as hot-plugged ones.
+pub struct Gamepads {
+    subsystem: GameControllerSubsystem,
+    /// Open controllers by instance id. Controllers stop reporting events once dropped.
+    open: HashMap<u32, GameController>,
+}
+
+impl Gamepads {
+    pub fn new(subsystem: GameControllerSubsystem) -> Gamepads {
+        Gamepads {
+            subsystem,
+            open: HashMap::new(),
+        }
+    }
+
+    /// Handle controller connections and stick movement, ignoring any other event
+    pub fn handle_event(&mut self, event: &Event, input: &mut InputState) {
+        match *event {
+            Event::ControllerDeviceAdded { which, .. } => match self.subsystem.open(which) {
+                Ok(controller) => {
+                    println!("Connected {}", controller.name());
+                    self.open.insert(controller.instance_id(), controller);
+                }
+                Err(e) => eprintln!("Could not open controller {}: {}", which, e),
+            },
+            Event::ControllerDeviceRemoved { which, .. } => {
+                if let Some(controller) = self.open.remove(&which) {
+                    println!("Disconnected {}", controller.name());
+                }
+                input.disconnect(which);
+            }
+            Event::ControllerAxisMotion {
+                which, axis, value, ..
+            } => input.tilt(which, axis, value),
+            _ => {}
+        }
+    }
+}
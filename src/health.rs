@@ -0,0 +1,43 @@
+use specs::prelude::*;
+
+use crate::components::*;
+
+use super::GameOver;
+
+pub struct HealthMonitor;
+
+/**
+ * Count down invulnerability after a hit, and end the game once a hero has no health left
+ */
+impl<'a> System<'a> for HealthMonitor {
+    type SystemData = (
+        Entities<'a>,
+        Write<'a, GameOver>,
+        ReadStorage<'a, Hero>,
+        ReadStorage<'a, Health>,
+        WriteStorage<'a, Invulnerable>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, mut game_over, heroes, healths, mut invulnerables): Self::SystemData,
+    ) {
+        let mut expired = Vec::new();
+        for (entity, invulnerable) in (&entities, &mut invulnerables).join() {
+            invulnerable.ticks_remaining = invulnerable.ticks_remaining.saturating_sub(1);
+            if invulnerable.ticks_remaining == 0 {
+                expired.push(entity);
+            }
+        }
+        for entity in expired {
+            invulnerables.remove(entity);
+        }
+
+        if (&heroes, &healths)
+            .join()
+            .any(|(_, health)| health.current == 0)
+        {
+            game_over.0 = true;
+        }
+    }
+}
@@ -0,0 +1,386 @@
+use sdl2::controller;
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::camera::CameraControl;
+use crate::components::Direction;
+use crate::vector::Vector2;
+
+/// Stick positions closer to the center than this (as a fraction of full tilt) are ignored
+const STICK_DEAD_ZONE: f32 = 0.25;
+
+/// Something the player can ask the game to do, independent of which key or button asks for it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    /// Start a game, or play again after a game over
+    Confirm,
+    /// Open the settings from the title screen
+    Settings,
+    /// Pause or resume a game, or go back from a menu
+    Pause,
+    /// Leave the current game, or the whole program from the title screen
+    Quit,
+    /// Freeze or unfreeze the simulation for debugging
+    Freeze,
+    /// Advance a frozen simulation by one tick
+    Step,
+    SlowDown,
+    SpeedUp,
+    /// Swap the hero between AI and keyboard control
+    ToggleControl,
+    Screenshot,
+    /// Show or hide hitboxes, AI targets, velocities, bounds and entity IDs
+    DebugOverlay,
+    /// Open or close the developer console
+    Console,
+    PanUp,
+    PanDown,
+    PanLeft,
+    PanRight,
+    ZoomIn,
+    ZoomOut,
+    /// Start (or stop) the camera following the hero
+    FollowHero,
+    /// Save the whole world to the quicksave snapshot
+    QuickSave,
+    /// Replace the world with the quicksave snapshot
+    QuickLoad,
+    /// While paused, go back a tick in the recorded history
+    RewindBack,
+    /// While paused, go forward a tick in the recorded history
+    RewindForward,
+}
+
+impl Action {
+    /// The direction moved in by a movement action
+    pub fn direction(self) -> Option<Direction> {
+        match self {
+            Action::MoveUp => Some(Direction::Up),
+            Action::MoveDown => Some(Direction::Down),
+            Action::MoveLeft => Some(Direction::Left),
+            Action::MoveRight => Some(Direction::Right),
+            _ => None,
+        }
+    }
+
+    /// The camera control performed by a camera action
+    pub fn camera_control(self) -> Option<CameraControl> {
+        match self {
+            Action::PanUp => Some(CameraControl::Pan(Direction::Up)),
+            Action::PanDown => Some(CameraControl::Pan(Direction::Down)),
+            Action::PanLeft => Some(CameraControl::Pan(Direction::Left)),
+            Action::PanRight => Some(CameraControl::Pan(Direction::Right)),
+            Action::ZoomIn => Some(CameraControl::ZoomIn),
+            Action::ZoomOut => Some(CameraControl::ZoomOut),
+            Action::FollowHero => Some(CameraControl::ToggleFollow),
+            _ => None,
+        }
+    }
+}
+
+/// The keys bound to each action when the config doesn't say otherwise
+const DEFAULT_KEY_BINDINGS: [(Action, &[&str]); 27] = [
+    (Action::MoveUp, &["Up", "W"]),
+    (Action::MoveDown, &["Down", "S"]),
+    (Action::MoveLeft, &["Left", "A"]),
+    (Action::MoveRight, &["Right", "D"]),
+    (Action::Confirm, &["Return", "R"]),
+    (Action::Settings, &["Tab"]),
+    (Action::Pause, &["Escape"]),
+    (Action::Quit, &["Q"]),
+    (Action::Freeze, &["P"]),
+    (Action::Step, &["N"]),
+    (Action::SlowDown, &["["]),
+    (Action::SpeedUp, &["]"]),
+    (Action::ToggleControl, &["C"]),
+    (Action::Screenshot, &["F12"]),
+    (Action::DebugOverlay, &["F3"]),
+    (Action::Console, &["`"]),
+    (Action::PanUp, &["I"]),
+    (Action::PanDown, &["K"]),
+    (Action::PanLeft, &["J"]),
+    (Action::PanRight, &["L"]),
+    (Action::ZoomIn, &["="]),
+    (Action::ZoomOut, &["-"]),
+    (Action::FollowHero, &["F"]),
+    (Action::QuickSave, &["F5"]),
+    (Action::QuickLoad, &["F9"]),
+    (Action::RewindBack, &[","]),
+    (Action::RewindForward, &["."]),
+];
+
+/// The game controller buttons bound to each action when the config doesn't say otherwise
+const DEFAULT_PAD_BINDINGS: [(Action, &[&str]); 27] = [
+    (Action::MoveUp, &["dpup"]),
+    (Action::MoveDown, &["dpdown"]),
+    (Action::MoveLeft, &["dpleft"]),
+    (Action::MoveRight, &["dpright"]),
+    (Action::Confirm, &["a"]),
+    (Action::Settings, &["y"]),
+    (Action::Pause, &["start", "b"]),
+    (Action::Quit, &["back"]),
+    (Action::Freeze, &["leftstick"]),
+    (Action::Step, &["rightstick"]),
+    (Action::SlowDown, &["leftshoulder"]),
+    (Action::SpeedUp, &["rightshoulder"]),
+    (Action::ToggleControl, &["x"]),
+    (Action::Screenshot, &["guide"]),
+    (Action::DebugOverlay, &[]),
+    (Action::Console, &[]),
+    (Action::PanUp, &[]),
+    (Action::PanDown, &[]),
+    (Action::PanLeft, &[]),
+    (Action::PanRight, &[]),
+    (Action::ZoomIn, &[]),
+    (Action::ZoomOut, &[]),
+    (Action::FollowHero, &[]),
+    (Action::QuickSave, &[]),
+    (Action::QuickLoad, &[]),
+    (Action::RewindBack, &[]),
+    (Action::RewindForward, &[]),
+];
+
+/// A key, or a button on any game controller, which can be bound to actions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Control {
+    Key(Keycode),
+    Pad(controller::Button),
+}
+
+/// A specific physical button, which can be held down
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    Key(Keycode),
+    Pad {
+        /// The instance id of the game controller the button is on
+        controller: u32,
+        button: controller::Button,
+    },
+}
+
+impl Button {
+    /// The button pressed (true) or released (false) by a key or game controller button event
+    pub fn from_event(event: &Event) -> Option<(Button, bool)> {
+        match *event {
+            Event::KeyDown {
+                keycode: Some(keycode),
+                repeat: false,
+                ..
+            } => Some((Button::Key(keycode), true)),
+            Event::KeyUp {
+                keycode: Some(keycode),
+                repeat: false,
+                ..
+            } => Some((Button::Key(keycode), false)),
+            Event::ControllerButtonDown { which, button, .. } => Some((
+                Button::Pad {
+                    controller: which,
+                    button,
+                },
+                true,
+            )),
+            Event::ControllerButtonUp { which, button, .. } => Some((
+                Button::Pad {
+                    controller: which,
+                    button,
+                },
+                false,
+            )),
+            _ => None,
+        }
+    }
+
+    /// What this button is bound as, whichever controller it's on
+    pub fn control(self) -> Control {
+        match self {
+            Button::Key(keycode) => Control::Key(keycode),
+            Button::Pad { button, .. } => Control::Pad(button),
+        }
+    }
+}
+
+/// Bind each action to the named controls, using the defaults unless overridden
+fn bind(
+    controls: &mut HashMap<Action, Vec<Control>>,
+    defaults: &[(Action, &[&str])],
+    overrides: &HashMap<Action, Vec<String>>,
+    parse: impl Fn(&str) -> Option<Control>,
+) -> Result<(), String> {
+    for (action, default_names) in defaults {
+        let names: Vec<&str> = match overrides.get(action) {
+            Some(names) => names.iter().map(String::as_str).collect(),
+            None => default_names.to_vec(),
+        };
+        for name in names {
+            let control = parse(name).ok_or(format!("Unknown {:?} binding {:?}", action, name))?;
+            controls.entry(*action).or_default().push(control);
+        }
+    }
+    Ok(())
+}
+
+/// Which actions each key and game controller button performs. A control may perform several
+/// actions, in which case the current game state decides which (if any) it cares about.
+pub struct Bindings {
+    actions: HashMap<Control, Vec<Action>>,
+    controls: HashMap<Action, Vec<Control>>,
+}
+
+impl Bindings {
+    /// The default bindings, with any action listed in the overrides bound to the keys (by SDL
+    /// key name) or buttons (by SDL game controller button name) listed for it instead
+    pub fn new(
+        key_overrides: &HashMap<Action, Vec<String>>,
+        pad_overrides: &HashMap<Action, Vec<String>>,
+    ) -> Result<Bindings, String> {
+        let mut controls = HashMap::new();
+        bind(
+            &mut controls,
+            &DEFAULT_KEY_BINDINGS,
+            key_overrides,
+            |name| Keycode::from_name(name).map(Control::Key),
+        )?;
+        bind(
+            &mut controls,
+            &DEFAULT_PAD_BINDINGS,
+            pad_overrides,
+            |name| controller::Button::from_string(name).map(Control::Pad),
+        )?;
+
+        let mut actions: HashMap<Control, Vec<Action>> = HashMap::new();
+        for (&action, action_controls) in &controls {
+            for &control in action_controls {
+                actions.entry(control).or_default().push(action);
+            }
+        }
+        Ok(Bindings { actions, controls })
+    }
+
+    /// Every action performed by the given control
+    pub fn actions(&self, control: Control) -> &[Action] {
+        self.actions.get(&control).map_or(&[], Vec::as_slice)
+    }
+
+    /// Whether the given control performs an action
+    pub fn performs(&self, control: Control, action: Action) -> bool {
+        self.actions(control).contains(&action)
+    }
+
+    /// The keys bound to an action, for showing to the player
+    pub fn describe(&self, action: Action) -> String {
+        let names: Vec<String> = self
+            .controls
+            .get(&action)
+            .into_iter()
+            .flatten()
+            .filter_map(|control| match control {
+                Control::Key(keycode) => Some(keycode.name()),
+                Control::Pad(_) => None,
+            })
+            .collect();
+        match names.is_empty() {
+            true => "(unbound)".to_string(),
+            false => names.join("/"),
+        }
+    }
+}
+
+/// The movement currently asked for, tracked across frames
+#[derive(Debug, Clone, Default)]
+pub struct InputState {
+    /// Held buttons and the direction each moves in, least recently pressed first
+    held: Vec<(Button, Direction)>,
+    /// The tilt of each game controller's movement stick, by controller instance id
+    sticks: HashMap<u32, Vector2>,
+}
+
+impl InputState {
+    pub fn press(&mut self, button: Button, direction: Direction) {
+        self.held.retain(|&(held, _)| held != button);
+        self.held.push((button, direction));
+    }
+
+    pub fn release(&mut self, button: Button) {
+        self.held.retain(|&(held, _)| held != button);
+    }
+
+    /// Set a stick axis from its raw SDL value
+    pub fn tilt(&mut self, controller: u32, axis: controller::Axis, value: i16) {
+        let tilt = (value as f32 / i16::MAX as f32).clamp(-1.0, 1.0);
+        let stick = self.sticks.entry(controller).or_default();
+        match axis {
+            controller::Axis::LeftX => stick.x = tilt,
+            controller::Axis::LeftY => stick.y = tilt,
+            _ => {}
+        }
+    }
+
+    /// Forget everything held on a controller which has been disconnected
+    pub fn disconnect(&mut self, controller: u32) {
+        self.sticks.remove(&controller);
+        self.held.retain(|&(held, _)| match held {
+            Button::Pad {
+                controller: held_controller,
+                ..
+            } => held_controller != controller,
+            Button::Key(_) => true,
+        });
+    }
+
+    /// Forget everything held, e.g. when button releases can no longer be seen
+    pub fn clear(&mut self) {
+        self.held.clear();
+        self.sticks.clear();
+    }
+
+    /// The heading to move along, at most a unit vector. Held buttons move at full speed, while
+    /// a partly tilted stick (when no buttons are held) moves proportionally slower.
+    pub fn heading(&self) -> Vector2 {
+        if self.held.is_empty() {
+            return self.stick_heading();
+        }
+        self.button_heading()
+    }
+
+    /// The heading of the most tilted stick, ignoring the dead zone
+    fn stick_heading(&self) -> Vector2 {
+        let stick = self
+            .sticks
+            .values()
+            .copied()
+            .max_by(|a, b| a.length().total_cmp(&b.length()))
+            .unwrap_or(Vector2::ZERO);
+        let tilt = stick.length().min(1.0);
+        match tilt {
+            tilt if tilt < STICK_DEAD_ZONE => Vector2::ZERO,
+            tilt => stick.normalized() * ((tilt - STICK_DEAD_ZONE) / (1.0 - STICK_DEAD_ZONE)),
+        }
+    }
+
+    /// When opposing directions are both held, the one pressed most recently wins
+    fn button_heading(&self) -> Vector2 {
+        let latest_of = |negative, positive| {
+            self.held
+                .iter()
+                .rev()
+                .map(|&(_, direction)| direction)
+                .find(|&direction| direction == negative || direction == positive)
+                .map_or(
+                    0.0,
+                    |direction| if direction == negative { -1.0 } else { 1.0 },
+                )
+        };
+        Vector2::new(
+            latest_of(Direction::Left, Direction::Right),
+            latest_of(Direction::Up, Direction::Down),
+        )
+        .normalized()
+    }
+}
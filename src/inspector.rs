@@ -0,0 +1,146 @@
+use specs::prelude::*;
+
+use crate::components::*;
+use crate::vector::Vector2;
+
+/// How much a single edit changes an entity's speed, in pixels per tick
+const SPEED_STEP: f32 = 0.5;
+/// How much a single edit turns an entity, in degrees
+const TURN_STEP: f32 = 15.0;
+
+/// A change to the selected entity's movement, made from the inspector while paused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InspectorEdit {
+    Faster,
+    Slower,
+    TurnLeft,
+    TurnRight,
+}
+
+/// The entity being inspected, if one has been selected
+pub fn selected(world: &World) -> Option<Entity> {
+    let (entities, selected): (Entities, ReadStorage<Selected>) = world.system_data();
+    (&entities, &selected)
+        .join()
+        .map(|(entity, _)| entity)
+        .next()
+}
+
+/// Every component of an entity, one per line
+pub fn describe(world: &World, entity: Entity) -> String {
+    let mut lines = vec![format!("Entity {}v{}", entity.id(), entity.gen().id())];
+
+    let mut tags = Vec::new();
+    if world.read_storage::<Hero>().contains(entity) {
+        tags.push("Hero");
+    }
+    if world.read_storage::<Enemy>().contains(entity) {
+        tags.push("Enemy");
+    }
+    if world.read_storage::<AIControlled>().contains(entity) {
+        tags.push("AI");
+    }
+    if world.read_storage::<KeyboardControlled>().contains(entity) {
+        tags.push("Keyboard");
+    }
+    if !tags.is_empty() {
+        lines.push(tags.join(", "));
+    }
+    if let Some(role) = world.read_storage::<EnemyRole>().get(entity) {
+        lines.push(format!("Role: {:?}", role));
+    }
+    if let Some(pos) = world.read_storage::<Position>().get(entity) {
+        lines.push(format!("Position: ({:.1}, {:.1})", pos.0.x, pos.0.y));
+    }
+    if let Some(vel) = world.read_storage::<Velocity>().get(entity) {
+        lines.push(format!(
+            "Velocity: ({:.2}, {:.2})\n  speed {:.2}, direction {:.0}°",
+            vel.0.x,
+            vel.0.y,
+            vel.0.length(),
+            vel.0.angle().to_degrees()
+        ));
+    }
+    if let Some(steer) = world.read_storage::<Steering>().get(entity) {
+        lines.push(format!(
+            "Steering: max speed {:.2}, max force {:.2}",
+            steer.max_speed, steer.max_force
+        ));
+        for (target, target_entity) in &steer.targets {
+            lines.push(format!(
+                "  {:?} target: {}v{}",
+                target,
+                target_entity.id(),
+                target_entity.gen().id()
+            ));
+        }
+    }
+    if let Some(perception) = world.read_storage::<Perception>().get(entity) {
+        lines.push(format!(
+            "Perception: radius {:.0}, line of sight {}",
+            perception.radius, perception.line_of_sight
+        ));
+    }
+    if let Some(health) = world.read_storage::<Health>().get(entity) {
+        lines.push(format!("Health: {}/{}", health.current, health.max));
+    }
+    if let Some(damage) = world.read_storage::<Damage>().get(entity) {
+        lines.push(format!("Damage: {}", damage.0));
+    }
+    if let Some(invulnerable) = world.read_storage::<Invulnerable>().get(entity) {
+        lines.push(format!(
+            "Invulnerable: {} ticks",
+            invulnerable.ticks_remaining
+        ));
+    }
+    if let Some(obstacle) = world.read_storage::<Obstacle>().get(entity) {
+        lines.push(format!("Obstacle: radius {:.1}", obstacle.radius));
+    }
+    if let Some(sprite) = world.read_storage::<Sprite>().get(entity) {
+        let region = sprite.region;
+        lines.push(format!(
+            "Sprite: sheet {}, region ({}, {}) {}x{}",
+            sprite.spritesheet,
+            region.x(),
+            region.y(),
+            region.width(),
+            region.height()
+        ));
+    }
+    if let Some(animation) = world.read_storage::<MovementAnimation>().get(entity) {
+        lines.push(format!("Animation frame: {}", animation.current_frame));
+    }
+    lines.join("\n")
+}
+
+/// Change the speed or direction of the selected entity. Steering entities have their maximum
+/// speed changed too, so the AI doesn't immediately undo the edit.
+pub fn apply(world: &mut World, edit: InspectorEdit) {
+    let entity = match selected(world) {
+        Some(entity) => entity,
+        None => return,
+    };
+    let mut velocities = world.write_storage::<Velocity>();
+    let vel = match velocities.get_mut(entity) {
+        Some(vel) => vel,
+        None => return,
+    };
+
+    let speed = vel.0.length();
+    let angle = vel.0.angle();
+    let (speed, angle) = match edit {
+        InspectorEdit::Faster => (speed + SPEED_STEP, angle),
+        InspectorEdit::Slower => ((speed - SPEED_STEP).max(0.0), angle),
+        InspectorEdit::TurnLeft => (speed, angle - TURN_STEP.to_radians()),
+        InspectorEdit::TurnRight => (speed, angle + TURN_STEP.to_radians()),
+    };
+    vel.0 = Vector2::from_angle(angle) * speed;
+
+    if let Some(steer) = world.write_storage::<Steering>().get_mut(entity) {
+        steer.max_speed = match edit {
+            InspectorEdit::Faster => steer.max_speed.max(speed),
+            InspectorEdit::Slower => speed,
+            InspectorEdit::TurnLeft | InspectorEdit::TurnRight => steer.max_speed,
+        };
+    }
+}
@@ -1,36 +1,23 @@
 use specs::prelude::*;
 
 use crate::components::*;
+use crate::input::InputState;
 
-use super::MovementCommand;
-
-const PLAYER_MOVEMENT_SPEED: i32 = 20;
+const PLAYER_MOVEMENT_SPEED: f32 = 20.0;
 
 pub struct Keyboard;
 
 impl<'a> System<'a> for Keyboard {
     type SystemData = (
-        ReadExpect<'a, Option<MovementCommand>>,
+        ReadExpect<'a, InputState>,
         ReadStorage<'a, KeyboardControlled>,
         WriteStorage<'a, Velocity>,
     );
 
-    fn run(&mut self, mut data: Self::SystemData) {
-        //TODO: This code can be made nicer and more idiomatic using more pattern matching.
-        // Look up "rust irrefutable patterns" and use them here.
-        let movement_command = match &*data.0 {
-            Some(movement_command) => movement_command,
-            None => return, // no change
-        };
-
-        for (_, vel) in (&data.1, &mut data.2).join() {
-            match movement_command {
-                &MovementCommand::Move(direction) => {
-                    vel.speed = PLAYER_MOVEMENT_SPEED;
-                    vel.direction = direction;
-                },
-                MovementCommand::Stop => vel.speed = 0,
-            }
+    fn run(&mut self, (input, keyboard_controlled, mut velocities): Self::SystemData) {
+        let heading = input.heading();
+        for (_, vel) in (&keyboard_controlled, &mut velocities).join() {
+            vel.0 = heading * PLAYER_MOVEMENT_SPEED;
         }
     }
-}
\ No newline at end of file
+}
@@ -1,35 +1,84 @@
 mod ai;
 mod animator;
+mod archetype;
+mod arena;
+mod boundary;
+mod camera;
+mod clock;
 mod components;
+mod config;
+mod console;
 mod enemy_collider_purger;
-mod enemy_oob_purger;
 mod enemy_spawner;
+mod gamepad;
+mod health;
+mod input;
+mod inspector;
 mod keyboard;
+mod map;
+mod mouse;
+mod neighbourhood;
 mod physics;
+mod prefab;
 mod renderer;
+mod rewind;
+mod snapshot;
+mod spawn_rules;
 mod sprite;
+mod state;
+mod steering;
+mod vector;
 
 use rand::prelude::*;
-use std::time::{Duration, Instant};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use sdl2::event::WindowEvent;
 use sdl2::pixels::Color;
-use sdl2::rect::{Point, Rect};
 // "self" imports the "image" module itself as well as everything else we listed
-use sdl2::image::{self, InitFlag, LoadTexture};
+use sdl2::image::{self, InitFlag};
 
 use specs::prelude::*;
 
+use crate::archetype::Archetypes;
+use crate::arena::ArenaConfig;
+use crate::camera::Camera;
+use crate::clock::{SimulationClock, TICKS_PER_SECOND};
 use crate::components::*;
+use crate::config::Config;
+use crate::console::Console;
+use crate::gamepad::Gamepads;
+use crate::input::{Action, Bindings, Button, Control, InputState};
+use crate::map::TileMap;
+use crate::mouse::MouseTool;
+use crate::prefab::{Prefab, Prefabs, HERO_PREFAB};
+use crate::rewind::History;
+use crate::snapshot::Snapshot;
+use crate::spawn_rules::SpawnRules;
+use crate::sprite::Spritesheets;
+use crate::state::{GameState, StateStack, Transition};
+use crate::vector::Vector2;
 
-pub enum MovementCommand {
-    Stop,
-    Move(Direction),
-}
+/// Set once the hero has been defeated, at which point the game is over
+#[derive(Default)]
+pub struct GameOver(pub bool);
+
+/// The source of all randomness in the simulation, so a seeded game plays out the same way
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GameRng(pub ChaCha8Rng);
 
-pub const WORLD_WIDTH: u32 = 800;
-pub const WORLD_HEIGHT: u32 = 600;
+impl GameRng {
+    pub fn new(seed: Option<u64>) -> GameRng {
+        match seed {
+            Some(seed) => GameRng(ChaCha8Rng::seed_from_u64(seed)),
+            None => GameRng(ChaCha8Rng::from_entropy()),
+        }
+    }
+}
 
 #[macro_export]
 macro_rules! rect(
@@ -38,57 +87,188 @@ macro_rules! rect(
     )
 );
 
-fn initialize_player(world: &mut World, player_spritesheet: usize) {
-    let player_top_left_frame =
-        Rect::new(0, 0, sprite::HERO_FRAME_WIDTH, sprite::HERO_FRAME_HEIGHT);
-
-    let player_animation = MovementAnimation {
-        current_frame: 0,
-        up_frames: sprite::character_animation_frames(
-            player_spritesheet,
-            player_top_left_frame,
-            Direction::Up,
-        ),
-        down_frames: sprite::character_animation_frames(
-            player_spritesheet,
-            player_top_left_frame,
-            Direction::Down,
-        ),
-        left_frames: sprite::character_animation_frames(
-            player_spritesheet,
-            player_top_left_frame,
-            Direction::Left,
-        ),
-        right_frames: sprite::character_animation_frames(
-            player_spritesheet,
-            player_top_left_frame,
-            Direction::Right,
-        ),
+/// Create the hero from its prefab, at the map's starting point or a random position
+fn initialize_player(world: &mut World, config: &Config) -> Result<(), String> {
+    let hero_start = world.read_resource::<TileMap>().hero_start;
+    let position = hero_start.unwrap_or_else(|| {
+        let rng = &mut world.write_resource::<GameRng>().0;
+        Vector2::new(rng.gen_range(-200.0..200.0), rng.gen_range(-200.0..200.0))
+    });
+    let overrides = Prefab {
+        control: Some(config.hero_control),
+        health: Some(config.hero_health),
+        ..Prefab::at(position)
     };
+    let hero = world
+        .read_resource::<Prefabs>()
+        .get(HERO_PREFAB)?
+        .merged(&overrides);
+    hero.build(world.create_entity(), config);
+    Ok(())
+}
 
-    world
-        .create_entity()
-        .with(AIControlled)
-        .with(Hero)
-        .with(Position(Point::new(
-            thread_rng().gen_range(-200..200),
-            thread_rng().gen_range(-200..200),
-        )))
-        .with(Velocity {
-            speed: 0,
-            direction: Direction::Right,
-        })
-        .with(Telemetry {
-            enemy_collisions: 0,
-            enemy_oob: 0,
-            enemy_spawned: 0,
+/// Place the map's obstacles for the AI to steer around
+fn initialize_map_obstacles(world: &mut World) {
+    let obstacles = world.read_resource::<TileMap>().obstacles.clone();
+    for (center, radius) in obstacles {
+        world
+            .create_entity()
+            .with(Obstacle { radius })
+            .with(Position(center))
+            .build();
+    }
+}
+
+/// Scatter a few static obstacles around the arena for the AI to steer around. Like a map's
+/// obstacles, they only steer the AI (and block enemies' line of sight): nothing collides with
+/// them.
+fn initialize_obstacles(world: &mut World) {
+    const OBSTACLE_COUNT: usize = 4;
+    for _ in 0..OBSTACLE_COUNT {
+        let (radius, position) = {
+            let rng = &mut world.write_resource::<GameRng>().0;
+            let radius = rng.gen_range(15.0..40.0);
+            let position = Vector2::new(rng.gen_range(-250.0..250.0), rng.gen_range(-200.0..200.0));
+            (radius, position)
+        };
+        world
+            .create_entity()
+            .with(Obstacle { radius })
+            .with(Position(position))
+            .build();
+    }
+}
+
+/// Swap every hero between AI and keyboard control, bringing it to a stop
+fn toggle_hero_control(world: &mut World) {
+    let (entities, heroes, mut ai_controlled, mut keyboard_controlled, mut velocities): (
+        Entities,
+        ReadStorage<Hero>,
+        WriteStorage<AIControlled>,
+        WriteStorage<KeyboardControlled>,
+        WriteStorage<Velocity>,
+    ) = world.system_data();
+
+    for (hero, _, vel) in (&entities, &heroes, &mut velocities).join() {
+        if ai_controlled.remove(hero).is_some() {
+            keyboard_controlled
+                .insert(hero, KeyboardControlled)
+                .unwrap();
+        } else {
+            keyboard_controlled.remove(hero);
+            ai_controlled.insert(hero, AIControlled).unwrap();
+        }
+        vel.0 = Vector2::ZERO;
+    }
+}
+
+/// How far (in pixels) the largest hitbox of anything which can be created reaches from its
+/// entity's position
+fn body_reach(prefabs: &Prefabs, archetypes: &Archetypes) -> u32 {
+    let archetype_prefabs = archetypes
+        .archetypes
+        .iter()
+        .map(|archetype| &archetype.prefab);
+    prefabs
+        .all()
+        .chain(archetype_prefabs)
+        .filter_map(Prefab::hitbox_rect)
+        .map(|hitbox| {
+            let sides = [hitbox.left(), hitbox.right(), hitbox.top(), hitbox.bottom()];
+            sides
+                .iter()
+                .map(|side| side.unsigned_abs())
+                .max()
+                .unwrap_or(0)
         })
-        .with(player_animation.right_frames[0].clone())
-        .with(player_animation)
-        .build();
+        .max()
+        .unwrap_or(0)
+}
+
+/// Generate an arena from the game's seed (or a random one), with room for hitboxes reaching
+/// `reach` pixels, exporting it if asked to
+fn generate_arena(
+    settings: &ArenaConfig,
+    seed: Option<u64>,
+    reach: u32,
+) -> Result<TileMap, String> {
+    let seed = seed.unwrap_or_else(random);
+    let map = arena::generate(settings, seed, reach)?;
+    println!(
+        "Generated {:?} arena from seed {}",
+        settings.generator, seed
+    );
+    if let Some(path) = &settings.export {
+        map.save(path)?;
+        println!("Exported arena to {}", path.display());
+    }
+    Ok(map)
+}
+
+/// Build a fresh world ready to play, with the map (if any), hero and obstacles in place
+fn initialize_world(
+    dispatcher: &mut Dispatcher,
+    config: &Config,
+    prefabs: &Prefabs,
+    archetypes: &Archetypes,
+    spritesheets: &Spritesheets,
+) -> Result<World, String> {
+    let mut world = World::new();
+    dispatcher.setup(&mut world);
+    renderer::SystemData::setup(&mut world);
+
+    // The arena is the size of the map, if there is one
+    let mut config = config.clone();
+    let map = match (&config.map, &config.arena) {
+        (Some(path), _) => TileMap::load(path)?,
+        (None, Some(arena)) => generate_arena(arena, config.seed, body_reach(prefabs, archetypes))?,
+        (None, None) => TileMap::default(),
+    };
+    let has_map = !map.layers.is_empty();
+    if has_map {
+        let (width, height) = map.size();
+        config.world_width = width;
+        config.world_height = height;
+    }
+
+    // Initialize resource
+    world.insert(InputState::default());
+    world.insert(GameRng::new(config.seed));
+    world.insert(Camera::new((config.window_width, config.window_height)));
+    world.insert(map);
+    world.insert(prefabs.clone());
+    world.insert(archetypes.clone());
+    world.insert(spritesheets.clone());
+    world.insert(match &config.spawn_rules {
+        Some(path) => SpawnRules::load(path)?,
+        None => SpawnRules::default(),
+    });
+
+    initialize_player(&mut world, &config)?;
+    match has_map {
+        true => initialize_map_obstacles(&mut world),
+        false => initialize_obstacles(&mut world),
+    }
+    world.insert(config);
+    Ok(world)
+}
+
+/// Seconds since the Unix epoch, for naming files uniquely
+fn unix_time_stamp() -> Result<u64, String> {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .map_err(|e| e.to_string())
 }
 
 fn main() -> Result<(), String> {
+    // The config file can be given as the first argument
+    let config_path = env::args()
+        .nth(1)
+        .map_or_else(|| PathBuf::from(config::DEFAULT_CONFIG_PATH), PathBuf::from);
+    // Settings start as loaded from the config file, and can be changed from the settings screen
+    let mut settings = Config::load(&config_path)?;
+
     let sdl_context = sdl2::init()?;
     let video_subsystem = sdl_context.video()?;
     // Leading "_" tells Rust that this is an unused variable that we don't care about. It has to
@@ -98,8 +278,9 @@ fn main() -> Result<(), String> {
     let ttf_context = sdl2::ttf::init().map_err(|e| e.to_string())?;
 
     let window = video_subsystem
-        .window("rusty-ai", WORLD_WIDTH, WORLD_HEIGHT)
+        .window("rusty-ai", settings.window_width, settings.window_height)
         .position_centered()
+        .resizable()
         .build()
         .expect("could not initialize video subsystem");
 
@@ -107,17 +288,17 @@ fn main() -> Result<(), String> {
         .into_canvas()
         .build()
         .expect("could not make a canvas");
+    if let Some((width, height)) = settings.logical_resolution {
+        canvas
+            .set_logical_size(width, height)
+            .map_err(|e| e.to_string())?;
+    }
     let texture_creator = canvas.texture_creator();
     let font = ttf_context.load_font("assets/fonts/Roboto/Roboto-Regular.ttf", 20)?;
     let mut dispatcher = DispatcherBuilder::new()
         .with(keyboard::Keyboard, "Keyboard", &[])
         .with(enemy_spawner::EnemySpawner, "EnemySpawner", &[])
         .with(ai::AI, "AI", &["EnemySpawner"])
-        .with(
-            enemy_oob_purger::EnemyOOBPurger,
-            "EnemyOOBPurger",
-            &["EnemySpawner", "AI"],
-        )
         .with(
             enemy_collider_purger::EnemyColliderPurger,
             "EnemyColliderPurger",
@@ -125,119 +306,235 @@ fn main() -> Result<(), String> {
         )
         .with(physics::Physics, "Physics", &["Keyboard", "AI"])
         .with(animator::Animator, "Animator", &["Keyboard", "AI"])
+        .with(
+            boundary::Boundary,
+            "Boundary",
+            &["Physics", "EnemyColliderPurger"],
+        )
+        .with(
+            health::HealthMonitor,
+            "HealthMonitor",
+            &["EnemyColliderPurger", "Boundary"],
+        )
         .build();
 
-    let mut world = World::new();
-    dispatcher.setup(&mut world);
-    renderer::SystemData::setup(&mut world);
-
-    // Initialize resource
-    let movement_command: Option<MovementCommand> = None;
-    world.insert(movement_command);
-
-    let mut textures = Vec::with_capacity(sprite::TEXTURE_PATHS.len());
-    for path in &sprite::TEXTURE_PATHS {
-        textures.push(texture_creator.load_texture(path)?)
-    }
-
-    // First texture in textures array
-    let player_spritesheet = 0;
+    let mut spritesheets = Spritesheets::default();
+    let prefabs = Prefabs::load(&settings.prefabs, &mut spritesheets)?;
+    let archetypes = Archetypes::load(settings.archetypes.as_deref(), &prefabs, &mut spritesheets)?;
+    let mut world = initialize_world(
+        &mut dispatcher,
+        &settings,
+        &prefabs,
+        &archetypes,
+        &spritesheets,
+    )?;
+    let mut states = StateStack::new(GameState::Title);
+    let mut clock = SimulationClock::new();
+    let mut history = History::new(settings.rewind_seconds);
+    let mut input = InputState::default();
+    let bindings = Bindings::new(&settings.key_bindings, &settings.pad_bindings)?;
+    let mut gamepads = Gamepads::new(sdl_context.game_controller()?);
+    let mut mouse = MouseTool::default();
+    let mut debug_overlay = false;
+    let mut console = Console::default();
+    let text_input = video_subsystem.text_input();
+    text_input.stop();
 
-    initialize_player(&mut world, player_spritesheet);
+    let mut textures = renderer::Textures::new();
 
     let mut event_pump = sdl_context.event_pump()?;
     let mut i = 0;
     'running: loop {
         let start_time = Instant::now();
 
-        // None - no change, Some(MovementCommand) - perform movement
-        let mut movement_command = None;
+        let mut screenshot_requested = false;
         // Handle events
         for event in event_pump.poll_iter() {
+            let mut transitions = Vec::new();
             match event {
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
-                    ..
-                } => {
-                    break 'running;
-                }
-                Event::KeyDown {
-                    keycode: Some(Keycode::Left),
-                    repeat: false,
-                    ..
-                } => {
-                    movement_command = Some(MovementCommand::Move(Direction::Left));
-                }
-                Event::KeyDown {
-                    keycode: Some(Keycode::Right),
-                    repeat: false,
-                    ..
-                } => {
-                    movement_command = Some(MovementCommand::Move(Direction::Right));
-                }
-                Event::KeyDown {
-                    keycode: Some(Keycode::Up),
-                    repeat: false,
+                Event::Quit { .. } => break 'running,
+                // Button releases aren't seen while the window is unfocused
+                Event::Window {
+                    win_event: WindowEvent::FocusLost,
                     ..
-                } => {
-                    movement_command = Some(MovementCommand::Move(Direction::Up));
-                }
+                } => input.clear(),
+                // While open, the console takes all typing except the key which closes it
                 Event::KeyDown {
-                    keycode: Some(Keycode::Down),
-                    repeat: false,
-                    ..
-                } => {
-                    movement_command = Some(MovementCommand::Move(Direction::Down));
-                }
-                Event::KeyUp {
-                    keycode: Some(Keycode::Left),
-                    repeat: false,
+                    keycode: Some(keycode),
                     ..
+                } if console.is_open()
+                    && !bindings.performs(Control::Key(keycode), Action::Console) =>
+                {
+                    if let Some(line) = console.handle_event(&event) {
+                        transitions.push(console.execute(&line, &mut world, &mut settings));
+                    }
                 }
-                | Event::KeyUp {
-                    keycode: Some(Keycode::Right),
-                    repeat: false,
-                    ..
-                }
-                | Event::KeyUp {
-                    keycode: Some(Keycode::Up),
-                    repeat: false,
-                    ..
+                Event::TextInput { .. } if console.is_open() => {
+                    console.handle_event(&event);
                 }
-                | Event::KeyUp {
-                    keycode: Some(Keycode::Down),
-                    repeat: false,
-                    ..
-                } => {
-                    movement_command = Some(MovementCommand::Stop);
+                // Track held movement whatever the current state, so it's right on resuming
+                event => match Button::from_event(&event) {
+                    Some((button, false)) => input.release(button),
+                    Some((button, true)) => {
+                        for &action in bindings.actions(button.control()) {
+                            if let Some(direction) = action.direction() {
+                                input.press(button, direction);
+                            }
+
+                            // Screenshots and debugging aids are available whatever the state
+                            match action {
+                                Action::Screenshot => screenshot_requested = true,
+                                Action::DebugOverlay => debug_overlay = !debug_overlay,
+                                Action::Console => {
+                                    console.toggle();
+                                    input.clear();
+                                }
+                                _ => transitions
+                                    .push(states.top_mut().handle_action(action, &mut settings)),
+                            }
+                        }
+                    }
+                    None => {
+                        gamepads.handle_event(&event, &mut input);
+                        if states.top().edits_world() {
+                            mouse.handle_event(&event, &mut world);
+                        }
+                    }
+                },
+            }
+
+            for transition in transitions {
+                match transition {
+                    Transition::Stay => {}
+                    Transition::Clock(control) => clock.apply(control),
+                    Transition::ToggleHeroControl => toggle_hero_control(&mut world),
+                    Transition::Inspect(edit) => inspector::apply(&mut world, edit),
+                    Transition::Camera(control) => {
+                        let hero = {
+                            let (entities, heroes): (Entities, ReadStorage<Hero>) =
+                                world.system_data();
+                            (&entities, &heroes).join().map(|(hero, _)| hero).next()
+                        };
+                        world.write_resource::<Camera>().apply(control, hero);
+                    }
+                    Transition::Save => {
+                        match Snapshot::save(&world, Path::new(snapshot::QUICKSAVE_PATH)) {
+                            Ok(()) => println!("Saved {}", snapshot::QUICKSAVE_PATH),
+                            Err(e) => eprintln!("Couldn't save: {}", e),
+                        }
+                    }
+                    Transition::Load => {
+                        match Snapshot::load(&mut world, Path::new(snapshot::QUICKSAVE_PATH)) {
+                            Ok(()) => {
+                                history.clear();
+                                mouse = MouseTool::default();
+                            }
+                            Err(e) => eprintln!("Couldn't load: {}", e),
+                        }
+                    }
+                    Transition::Loaded => {
+                        // Play on from the loaded situation, whatever screen it was loaded from
+                        history.clear();
+                        mouse = MouseTool::default();
+                        states.reset(GameState::Playing);
+                    }
+                    Transition::Rewind(control) => {
+                        if history.apply(control, &mut world) {
+                            mouse = MouseTool::default();
+                        }
+                    }
+                    Transition::Push(state) => states.push(state),
+                    Transition::Pop => states.pop(),
+                    Transition::Reset(state) => states.reset(state),
+                    Transition::NewGame => {
+                        // Snapshots may have added spritesheets, so keep them numbered
+                        let spritesheets = (*world.read_resource::<Spritesheets>()).clone();
+                        world = initialize_world(
+                            &mut dispatcher,
+                            &settings,
+                            &prefabs,
+                            &archetypes,
+                            &spritesheets,
+                        )?;
+                        history.clear();
+                        mouse = MouseTool::default();
+                        states.reset(GameState::Playing);
+                    }
+                    Transition::Quit => break 'running,
                 }
-                _ => {}
             }
         }
-
-        *world.write_resource() = movement_command;
+        // Only deliver typed text while there's somewhere to type it
+        if console.is_open() != text_input.is_active() {
+            match console.is_open() {
+                true => text_input.start(),
+                false => text_input.stop(),
+            }
+        }
+        *world.write_resource() = input.clone();
 
         // Update
         i = (i + 1) % 255;
-        dispatcher.dispatch(&mut world);
-        world.maintain();
+        if states.top().runs_dispatcher() {
+            for _ in 0..clock.ticks_this_frame() {
+                dispatcher.dispatch(&world);
+                world.maintain();
+                mouse.hold(&world);
+                history.record(&world);
+                if world.read_resource::<GameOver>().0 {
+                    states.reset(GameState::GameOver);
+                    break;
+                }
+            }
+        }
 
-        // Render
+        // Render, first loading any images new games or snapshots have brought in
+        textures.load_spritesheets(&texture_creator, &world.read_resource())?;
+        textures.load_tilesets(&texture_creator, &world.read_resource())?;
+        {
+            let mut camera = world.write_resource::<Camera>();
+            camera.viewport = renderer::viewport(&canvas)?;
+            camera.update(&world.read_storage::<Position>());
+        }
+        let overlay = {
+            let telemetries = world.read_storage::<Telemetry>();
+            states
+                .top()
+                .overlay(&settings, &bindings, (&telemetries).join().last())
+        };
+        let clock_status = match history.status() {
+            Some(rewound) => format!("{} - {}", clock.status(), rewound),
+            None => clock.status(),
+        };
+        let inspector =
+            inspector::selected(&world).map(|entity| inspector::describe(&world, entity));
         renderer::render(
             &mut canvas,
             Color::RGB(i, 64, 255 - i),
             &textures,
             &texture_creator,
             &font,
+            &renderer::Hud {
+                clock_status: &clock_status,
+                overlay: overlay.as_deref(),
+                inspector: inspector.as_deref(),
+                debug: debug_overlay,
+                console: console.text().as_deref(),
+            },
             world.system_data(),
         )?;
+        if screenshot_requested {
+            let path = format!("screenshot-{}.bmp", unix_time_stamp()?);
+            renderer::save_screenshot(&canvas, Path::new(&path))?;
+            println!("Saved {}", path);
+        }
+        canvas.present();
 
         let end_time = Instant::now();
         let difference = end_time.duration_since(start_time);
         println!("frame_time: {:?}", difference);
         // Time management!
-        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 20));
+        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / TICKS_PER_SECOND));
     }
 
     Ok(())
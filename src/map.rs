@@ -0,0 +1,533 @@
+use sdl2::rect::Rect;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::vector::Vector2;
+
+/// Tiled stores flip and rotation flags in the top bits of each tile id
+const TILE_ID_MASK: u32 = 0x1fff_ffff;
+/// The name of the (boolean) property marking a tile, or a whole tile layer, as solid
+const COLLIDES_PROPERTY: &str = "collides";
+/// The tileset generated maps are drawn with, and its floor and wall tiles
+const GENERATED_TILESET: &str = "assets/darkdimension.png";
+const GENERATED_TILESET_COLUMNS: u32 = 29;
+pub const GENERATED_TILE_SIZE: u32 = 16;
+const FLOOR_TILE: u32 = 293;
+const WALL_TILE: u32 = 129;
+
+/// The path to `target` from `directory`, both absolute. Paths without a common root (such as on
+/// different drives) are left absolute.
+fn relative_path(directory: &Path, target: &Path) -> PathBuf {
+    let directory: Vec<Component> = directory.components().collect();
+    let target: Vec<Component> = target.components().collect();
+    let common = directory
+        .iter()
+        .zip(&target)
+        .take_while(|(a, b)| a == b)
+        .count();
+    if common == 0 {
+        return target.iter().collect();
+    }
+    let ups = directory[common..].iter().map(|_| Component::ParentDir);
+    ups.chain(target[common..].iter().copied()).collect()
+}
+
+/// The parts of a Tiled JSON map this game understands
+#[derive(Deserialize)]
+struct TiledMap {
+    width: u32,
+    height: u32,
+    tilewidth: u32,
+    tileheight: u32,
+    layers: Vec<TiledLayer>,
+    tilesets: Vec<TiledTileset>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum TiledLayer {
+    TileLayer {
+        name: String,
+        data: Vec<u32>,
+        #[serde(default = "visible")]
+        visible: bool,
+        #[serde(default)]
+        properties: Vec<TiledProperty>,
+    },
+    ObjectGroup {
+        objects: Vec<TiledObject>,
+    },
+    #[serde(other)]
+    Unsupported,
+}
+
+fn visible() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+struct TiledProperty {
+    name: String,
+    value: serde_json::Value,
+}
+
+/// Whether the properties mark something as solid
+fn collides(properties: &[TiledProperty]) -> bool {
+    properties
+        .iter()
+        .any(|property| property.name == COLLIDES_PROPERTY && property.value == true)
+}
+
+#[derive(Deserialize)]
+struct TiledObject {
+    /// Tiled calls this "class" from version 1.9
+    #[serde(rename = "type", alias = "class", default)]
+    kind: String,
+    x: f32,
+    y: f32,
+    #[serde(default)]
+    width: f32,
+    #[serde(default)]
+    height: f32,
+}
+
+#[derive(Deserialize)]
+struct TiledTileset {
+    firstgid: u32,
+    /// Only set for tilesets stored in their own file, which aren't supported
+    source: Option<String>,
+    image: Option<String>,
+    columns: Option<u32>,
+    tilewidth: Option<u32>,
+    tileheight: Option<u32>,
+    #[serde(default)]
+    tiles: Vec<TiledTile>,
+}
+
+#[derive(Deserialize)]
+struct TiledTile {
+    id: u32,
+    #[serde(default)]
+    properties: Vec<TiledProperty>,
+}
+
+/// An image cut into equally sized tiles
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tileset {
+    /// The map-wide id of the first tile in this tileset
+    pub first_gid: u32,
+    pub image: PathBuf,
+    pub columns: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    /// The (tileset local) ids of tiles which are solid wherever they're placed
+    solid_tiles: HashSet<u32>,
+}
+
+/// A layer of tiles, by map-wide tile id (0 for none), row by row
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TileLayer {
+    pub tiles: Vec<u32>,
+    pub visible: bool,
+}
+
+/// A grid of tiles drawn beneath everything else, some of which block movement. The map is
+/// centered on the world's (0, 0) coordinate. An empty map has no tiles and blocks nothing.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TileMap {
+    pub columns: u32,
+    pub rows: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub layers: Vec<TileLayer>,
+    pub tilesets: Vec<Tileset>,
+    /// Which tiles block movement, row by row
+    solid: Vec<bool>,
+    /// Where the hero starts, if the map says
+    pub hero_start: Option<Vector2>,
+    /// Areas (top left and bottom right corners) enemies spawn in, if the map has any
+    pub spawn_zones: Vec<(Vector2, Vector2)>,
+    /// Circular obstacles the AI steers around, as centers and radii
+    pub obstacles: Vec<(Vector2, f32)>,
+}
+
+impl TileMap {
+    /// Load a map saved by Tiled in its JSON format. Tile layers must use the default CSV
+    /// encoding and tilesets must be embedded in the map.
+    ///
+    /// The object layer places the hero ("hero" objects), enemy spawn zones ("spawn") and
+    /// obstacles ("obstacle"). Tiles are solid if their layer, or the tile itself in the
+    /// tileset, has a `collides` property set to true.
+    pub fn load(path: &Path) -> Result<TileMap, String> {
+        if path.extension().is_some_and(|extension| extension == "tmx") {
+            return Err(format!(
+                "{}: TMX maps aren't supported, export the map from Tiled as JSON",
+                path.display()
+            ));
+        }
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let tiled: TiledMap =
+            serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+
+        let mut map = TileMap {
+            columns: tiled.width,
+            rows: tiled.height,
+            tile_width: tiled.tilewidth,
+            tile_height: tiled.tileheight,
+            solid: vec![false; (tiled.width * tiled.height) as usize],
+            ..TileMap::default()
+        };
+
+        for tileset in tiled.tilesets {
+            if let Some(source) = tileset.source {
+                return Err(format!("External tileset {} isn't supported", source));
+            }
+            let image = tileset.image.ok_or("Tileset without an image")?;
+            map.tilesets.push(Tileset {
+                first_gid: tileset.firstgid,
+                image: directory.join(image),
+                columns: tileset.columns.ok_or("Tileset without columns")?,
+                tile_width: tileset.tilewidth.unwrap_or(tiled.tilewidth),
+                tile_height: tileset.tileheight.unwrap_or(tiled.tileheight),
+                solid_tiles: tileset
+                    .tiles
+                    .iter()
+                    .filter(|tile| collides(&tile.properties))
+                    .map(|tile| tile.id)
+                    .collect(),
+            });
+        }
+        // Tiles belong to the last tileset starting at or before their id
+        map.tilesets.sort_by_key(|tileset| tileset.first_gid);
+
+        let origin = map.origin();
+        for layer in tiled.layers {
+            match layer {
+                TiledLayer::TileLayer {
+                    name,
+                    data,
+                    visible,
+                    properties,
+                } => {
+                    if data.len() != map.solid.len() {
+                        return Err(format!("Layer {} is the wrong size", name));
+                    }
+                    let layer_collides = collides(&properties);
+                    for (i, &gid) in data.iter().enumerate() {
+                        if gid != 0 && (layer_collides || map.tile_is_solid(gid)) {
+                            map.solid[i] = true;
+                        }
+                    }
+                    map.layers.push(TileLayer {
+                        tiles: data,
+                        visible,
+                    });
+                }
+                TiledLayer::ObjectGroup { objects } => {
+                    for object in objects {
+                        let top_left = origin + Vector2::new(object.x, object.y);
+                        let size = Vector2::new(object.width, object.height);
+                        match object.kind.as_str() {
+                            "hero" => map.hero_start = Some(top_left + size / 2.0),
+                            "spawn" => map.spawn_zones.push((top_left, top_left + size)),
+                            "obstacle" => map
+                                .obstacles
+                                .push((top_left + size / 2.0, size.x.max(size.y) / 2.0)),
+                            other => eprintln!("Ignoring map object of type {:?}", other),
+                        }
+                    }
+                }
+                TiledLayer::Unsupported => eprintln!("Ignoring unsupported map layer"),
+            }
+        }
+        Ok(map)
+    }
+
+    /// A map of floor, with solid walls wherever `walls` (row by row) is true, drawn with the
+    /// built-in tileset
+    pub fn from_walls(columns: u32, rows: u32, walls: &[bool]) -> TileMap {
+        let wall_tiles = walls
+            .iter()
+            .map(|&wall| if wall { WALL_TILE } else { 0 })
+            .collect();
+        TileMap {
+            columns,
+            rows,
+            tile_width: GENERATED_TILE_SIZE,
+            tile_height: GENERATED_TILE_SIZE,
+            layers: vec![
+                TileLayer {
+                    tiles: vec![FLOOR_TILE; walls.len()],
+                    visible: true,
+                },
+                TileLayer {
+                    tiles: wall_tiles,
+                    visible: true,
+                },
+            ],
+            tilesets: vec![Tileset {
+                first_gid: 1,
+                image: PathBuf::from(GENERATED_TILESET),
+                columns: GENERATED_TILESET_COLUMNS,
+                tile_width: GENERATED_TILE_SIZE,
+                tile_height: GENERATED_TILE_SIZE,
+                solid_tiles: HashSet::new(),
+            }],
+            solid: walls.to_vec(),
+            ..TileMap::default()
+        }
+    }
+
+    /// Save the map in Tiled's JSON format, so it can be loaded again (or edited in Tiled).
+    /// Which tiles are solid is saved as an extra, hidden layer.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let mut layers: Vec<serde_json::Value> = self
+            .layers
+            .iter()
+            .enumerate()
+            .map(|(i, layer)| {
+                json!({
+                    "type": "tilelayer",
+                    "id": i + 1,
+                    "name": format!("layer {}", i + 1),
+                    "width": self.columns,
+                    "height": self.rows,
+                    "x": 0,
+                    "y": 0,
+                    "opacity": 1,
+                    "visible": layer.visible,
+                    "data": layer.tiles,
+                })
+            })
+            .collect();
+        let solid: Vec<u32> = self.solid.iter().map(|&solid| solid as u32).collect();
+        layers.push(json!({
+            "type": "tilelayer",
+            "id": layers.len() + 1,
+            "name": "collision",
+            "width": self.columns,
+            "height": self.rows,
+            "x": 0,
+            "y": 0,
+            "opacity": 1,
+            "visible": false,
+            "properties": [{ "name": COLLIDES_PROPERTY, "type": "bool", "value": true }],
+            "data": solid,
+        }));
+
+        // Objects are saved relative to the map's top left corner
+        let origin = self.origin();
+        let object = |kind: &str, top_left: Vector2, size: Vector2| {
+            let top_left = top_left - origin;
+            json!({
+                "type": kind,
+                "name": "",
+                "x": top_left.x,
+                "y": top_left.y,
+                "width": size.x,
+                "height": size.y,
+                "rotation": 0,
+                "visible": true,
+            })
+        };
+        let mut objects = Vec::new();
+        if let Some(start) = self.hero_start {
+            objects.push(object("hero", start, Vector2::ZERO));
+        }
+        for &(top_left, bottom_right) in &self.spawn_zones {
+            objects.push(object("spawn", top_left, bottom_right - top_left));
+        }
+        for &(center, radius) in &self.obstacles {
+            let half_size = Vector2::new(radius, radius);
+            objects.push(object("obstacle", center - half_size, half_size * 2.0));
+        }
+        for (i, object) in objects.iter_mut().enumerate() {
+            object["id"] = json!(i + 1);
+        }
+        let next_object_id = objects.len() + 1;
+        layers.push(json!({
+            "type": "objectgroup",
+            "id": layers.len() + 1,
+            "name": "objects",
+            "x": 0,
+            "y": 0,
+            "opacity": 1,
+            "visible": true,
+            "objects": objects,
+        }));
+
+        // Tileset images are loaded relative to the map, so save their paths from the map's
+        // directory (which lets the map be loaded from any checkout)
+        let directory = match path.parent() {
+            Some(parent) if parent != Path::new("") => parent,
+            _ => Path::new("."),
+        };
+        let directory =
+            fs::canonicalize(directory).map_err(|e| format!("{}: {}", directory.display(), e))?;
+        let tilesets = self
+            .tilesets
+            .iter()
+            .map(|tileset| {
+                let image = fs::canonicalize(&tileset.image)
+                    .map_err(|e| format!("{}: {}", tileset.image.display(), e))?;
+                let image = relative_path(&directory, &image);
+                let solid_tiles: Vec<serde_json::Value> = tileset
+                    .solid_tiles
+                    .iter()
+                    .map(|id| {
+                        json!({
+                            "id": id,
+                            "properties": [
+                                { "name": COLLIDES_PROPERTY, "type": "bool", "value": true }
+                            ],
+                        })
+                    })
+                    .collect();
+                Ok(json!({
+                    "firstgid": tileset.first_gid,
+                    "name": image.file_stem().map(|stem| stem.to_string_lossy()),
+                    "image": image,
+                    "columns": tileset.columns,
+                    "tilewidth": tileset.tile_width,
+                    "tileheight": tileset.tile_height,
+                    "margin": 0,
+                    "spacing": 0,
+                    "tiles": solid_tiles,
+                }))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let tiled = json!({
+            "type": "map",
+            "orientation": "orthogonal",
+            "renderorder": "right-down",
+            "infinite": false,
+            "width": self.columns,
+            "height": self.rows,
+            "tilewidth": self.tile_width,
+            "tileheight": self.tile_height,
+            "nextlayerid": layers.len() + 1,
+            "nextobjectid": next_object_id,
+            "layers": layers,
+            "tilesets": tilesets,
+        });
+        let text = serde_json::to_string_pretty(&tiled).map_err(|e| e.to_string())?;
+        fs::write(path, text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// The map's size in world pixels
+    pub fn size(&self) -> (u32, u32) {
+        (self.columns * self.tile_width, self.rows * self.tile_height)
+    }
+
+    /// The world position of the map's top left corner
+    pub fn origin(&self) -> Vector2 {
+        let (width, height) = self.size();
+        -Vector2::new(width as f32, height as f32) / 2.0
+    }
+
+    /// The tileset a map-wide tile id belongs to, and the tile's region of the tileset image
+    pub fn tile_source(&self, gid: u32) -> Option<(&Tileset, Rect)> {
+        let gid = gid & TILE_ID_MASK;
+        let tileset = self
+            .tilesets
+            .iter()
+            .rev()
+            .find(|tileset| tileset.first_gid <= gid)?;
+        let local = gid - tileset.first_gid;
+        let region = Rect::new(
+            ((local % tileset.columns) * tileset.tile_width) as i32,
+            ((local / tileset.columns) * tileset.tile_height) as i32,
+            tileset.tile_width,
+            tileset.tile_height,
+        );
+        Some((tileset, region))
+    }
+
+    fn tile_is_solid(&self, gid: u32) -> bool {
+        match self.tile_source(gid) {
+            Some((tileset, _)) => tileset
+                .solid_tiles
+                .contains(&((gid & TILE_ID_MASK) - tileset.first_gid)),
+            None => false,
+        }
+    }
+
+    /// The world rectangle covered by a tile
+    pub fn tile_rect(&self, column: u32, row: u32) -> Rect {
+        let origin = self.origin();
+        Rect::new(
+            origin.x as i32 + (column * self.tile_width) as i32,
+            origin.y as i32 + (row * self.tile_height) as i32,
+            self.tile_width,
+            self.tile_height,
+        )
+    }
+
+    /// The column and row of the tile at a world position, if it's on the map
+    pub fn tile_at(&self, position: Vector2) -> Option<(u32, u32)> {
+        if self.columns == 0 || self.rows == 0 {
+            return None;
+        }
+        let offset = position - self.origin();
+        let column = (offset.x / self.tile_width as f32).floor();
+        let row = (offset.y / self.tile_height as f32).floor();
+        if column < 0.0 || row < 0.0 || column >= self.columns as f32 || row >= self.rows as f32 {
+            return None;
+        }
+        Some((column as u32, row as u32))
+    }
+
+    /// Whether the tile at the given column and row blocks movement, for physics and
+    /// pathfinding. Anywhere off the map is open.
+    pub fn is_solid(&self, column: u32, row: u32) -> bool {
+        column < self.columns
+            && row < self.rows
+            && self.solid[(row * self.columns + column) as usize]
+    }
+
+    /// Whether a world position is inside a solid tile
+    pub fn collides(&self, position: Vector2) -> bool {
+        match self.tile_at(position) {
+            Some((column, row)) => self.is_solid(column, row),
+            None => false,
+        }
+    }
+
+    /// How much of a rectangle, relative to a world position, is inside solid tiles (in square
+    /// pixels). This is worked out exactly (in f64), so overlaps can be compared as something
+    /// moves.
+    pub fn solid_overlap(&self, position: Vector2, area: Rect) -> f64 {
+        let (width, height) = (self.tile_width as f64, self.tile_height as f64);
+        let origin = self.origin();
+        let from = (
+            position.x as f64 + area.x() as f64 - origin.x as f64,
+            position.y as f64 + area.y() as f64 - origin.y as f64,
+        );
+        let to = (from.0 + area.width() as f64, from.1 + area.height() as f64);
+        // The tiles the rectangle covers, clamped to the map
+        let tiles = |from: f64, to: f64, size: f64, count: u32| {
+            let first = (from / size).floor().max(0.0) as u32;
+            let last = ((to / size).ceil() as i64).clamp(0, count as i64) as u32;
+            first..last
+        };
+
+        let mut overlap = 0.0;
+        for row in tiles(from.1, to.1, height, self.rows) {
+            for column in tiles(from.0, to.0, width, self.columns) {
+                if !self.is_solid(column, row) {
+                    continue;
+                }
+                let (left, top) = (column as f64 * width, row as f64 * height);
+                let overlap_x = to.0.min(left + width) - from.0.max(left);
+                let overlap_y = to.1.min(top + height) - from.1.max(top);
+                overlap += overlap_x.max(0.0) * overlap_y.max(0.0);
+            }
+        }
+        overlap
+    }
+}
@@ -0,0 +1,125 @@
+use sdl2::event::Event;
+use sdl2::mouse::MouseButton;
+use sdl2::rect::Rect;
+use specs::prelude::*;
+
+use crate::camera::Camera;
+use crate::components::*;
+use crate::enemy_spawner;
+use crate::vector::Vector2;
+
+/// An entity being dragged with the mouse
+struct Drag {
+    entity: Entity,
+    /// Where the entity should be, relative to the cursor
+    offset: Vector2,
+    /// The world position of the cursor
+    cursor: Vector2,
+}
+
+/// Builds test scenarios by hand: clicking empty space spawns an enemy there, while clicking an
+/// entity selects it and dragging moves it. The wheel zooms the camera.
+#[derive(Default)]
+pub struct MouseTool {
+    drag: Option<Drag>,
+}
+
+impl MouseTool {
+    /// Handle mouse buttons and movement, ignoring any other event
+    pub fn handle_event(&mut self, event: &Event, world: &mut World) {
+        match *event {
+            Event::MouseButtonDown {
+                mouse_btn: MouseButton::Left,
+                x,
+                y,
+                ..
+            } => {
+                let cursor = world.read_resource::<Camera>().to_world(x, y);
+                world.write_storage::<Selected>().clear();
+                match entity_at(world, cursor) {
+                    Some(entity) => {
+                        world
+                            .write_storage::<Selected>()
+                            .insert(entity, Selected)
+                            .unwrap();
+                        let position = world.read_storage::<Position>().get(entity).unwrap().0;
+                        self.drag = Some(Drag {
+                            entity,
+                            offset: position - cursor,
+                            cursor,
+                        });
+                    }
+                    None => {
+                        enemy_spawner::spawn_enemy(world, cursor);
+                    }
+                }
+            }
+            Event::MouseMotion { x, y, .. } => {
+                if let Some(drag) = &mut self.drag {
+                    drag.cursor = world.read_resource::<Camera>().to_world(x, y);
+                }
+                self.hold(world);
+            }
+            Event::MouseButtonUp {
+                mouse_btn: MouseButton::Left,
+                ..
+            } => self.drag = None,
+            Event::MouseWheel { y, .. } => world.write_resource::<Camera>().zoom_by(y),
+            _ => {}
+        }
+    }
+
+    /// Keep the dragged entity under the cursor, however the simulation tried to move it
+    pub fn hold(&mut self, world: &World) {
+        let drag = match &self.drag {
+            Some(drag) => drag,
+            None => return,
+        };
+        // The entity may have been purged mid-drag
+        if !world.is_alive(drag.entity) {
+            self.drag = None;
+            return;
+        }
+        if let Some(pos) = world.write_storage::<Position>().get_mut(drag.entity) {
+            pos.0 = drag.cursor + drag.offset;
+        }
+        if let Some(vel) = world.write_storage::<Velocity>().get_mut(drag.entity) {
+            vel.0 = Vector2::ZERO;
+        }
+    }
+}
+
+/// The entity drawn at a world position. Sprites are drawn over obstacles, so are picked first.
+fn entity_at(world: &World, point: Vector2) -> Option<Entity> {
+    let (entities, positions, sprites, obstacles): (
+        Entities,
+        ReadStorage<Position>,
+        ReadStorage<Sprite>,
+        ReadStorage<Obstacle>,
+    ) = world.system_data();
+    let point = Position(point).to_point();
+
+    let sprite_hit = (&entities, &positions, &sprites)
+        .join()
+        .filter(|(_, pos, sprite)| {
+            Rect::from_center(
+                pos.to_point(),
+                sprite.region.width(),
+                sprite.region.height(),
+            )
+            .contains_point(point)
+        })
+        .map(|(entity, _, _)| entity)
+        .last();
+    let obstacle_hit = || {
+        (&entities, &positions, &obstacles)
+            .join()
+            .filter(|(_, pos, obstacle)| {
+                let diameter = (obstacle.radius * 2.0) as u32;
+                Rect::from_center(pos.to_point(), diameter, diameter).contains_point(point)
+            })
+            .map(|(entity, _, _)| entity)
+            .last()
+    };
+    sprite_hit.or_else(obstacle_hit)
+}
@@ -0,0 +1,53 @@
+use specs::Entity;
+use std::collections::HashMap;
+
+use crate::steering::Body;
+use crate::vector::Vector2;
+
+/// A spatial hash of bodies, so finding everything near a point doesn't mean checking every body
+pub struct Neighbourhood {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<(Entity, Body)>>,
+}
+
+impl Neighbourhood {
+    /// Queries are cheapest when `cell_size` is close to the radius usually queried
+    pub fn new(cell_size: f32) -> Neighbourhood {
+        Neighbourhood {
+            cell_size,
+            cells: HashMap::new(),
+        }
+    }
+
+    fn cell(&self, position: Vector2) -> (i32, i32) {
+        (
+            (position.x / self.cell_size).floor() as i32,
+            (position.y / self.cell_size).floor() as i32,
+        )
+    }
+
+    pub fn insert(&mut self, entity: Entity, body: Body) {
+        let cell = self.cell(body.position);
+        self.cells.entry(cell).or_default().push((entity, body));
+    }
+
+    /// Every body within `radius` of `position` (including any body at `position` itself)
+    pub fn query(&self, position: Vector2, radius: f32) -> Vec<(Entity, Body)> {
+        let (min_x, min_y) = self.cell(position - Vector2::new(radius, radius));
+        let (max_x, max_y) = self.cell(position + Vector2::new(radius, radius));
+
+        let mut found = Vec::new();
+        for x in min_x..=max_x {
+            for y in min_y..=max_y {
+                if let Some(bodies) = self.cells.get(&(x, y)) {
+                    found.extend(
+                        bodies
+                            .iter()
+                            .filter(|(_, body)| body.position.distance(position) <= radius),
+                    );
+                }
+            }
+        }
+        found
+    }
+}
@@ -1,27 +1,59 @@
 use specs::prelude::*;
 
 use crate::components::*;
+use crate::map::TileMap;
+use crate::vector::Vector2;
 
 pub struct Physics;
 
+/// How far into solid tiles an entity at `position` is: the area of its hitbox inside them, or
+/// without a hitbox, whether its position is inside one
+fn overlap(map: &TileMap, position: Vector2, hitbox: Option<&Hitbox>) -> f64 {
+    match hitbox {
+        Some(Hitbox(rect)) => map.solid_overlap(position, *rect),
+        None if map.collides(position) => 1.0,
+        None => 0.0,
+    }
+}
+
+/**
+ * Move everything along its velocity, sliding along (rather than passing through) solid map
+ * tiles. Fast movers are moved in steps of at most half a tile so they can't skip over walls.
+ * Anything with a hitbox is blocked once any part of the hitbox would be inside a wall.
+ * Anything already overlapping a wall (such as something placed against one) can only make
+ * moves which don't take it further in, so it can get clear but never pass through.
+ */
 impl<'a> System<'a> for Physics {
-    type SystemData = (WriteStorage<'a, Position>, ReadStorage<'a, Velocity>);
+    type SystemData = (
+        Read<'a, TileMap>,
+        ReadStorage<'a, Hitbox>,
+        WriteStorage<'a, Position>,
+        WriteStorage<'a, Velocity>,
+    );
 
-    fn run(&mut self, mut data: Self::SystemData) {
-        use self::Direction::*;
-        for (pos, vel) in (&mut data.0, &data.1).join() {
-            match vel.direction {
-                Left => {
-                    pos.0 = pos.0.offset(-vel.speed, 0);
-                }
-                Right => {
-                    pos.0 = pos.0.offset(vel.speed, 0);
-                }
-                Up => {
-                    pos.0 = pos.0.offset(0, -vel.speed);
+    fn run(&mut self, (map, hitboxes, mut positions, mut velocities): Self::SystemData) {
+        let max_step = map.tile_width.min(map.tile_height) as f32 / 2.0;
+        for (pos, vel, hitbox) in (&mut positions, &mut velocities, hitboxes.maybe()).join() {
+            if map.layers.is_empty() {
+                pos.0 += vel.0;
+                continue;
+            }
+
+            let steps = (vel.0.length() / max_step).ceil().max(1.0);
+            let step = vel.0 / steps;
+            for _ in 0..steps as u32 {
+                // Try each axis separately, so blocked movement slides along walls
+                let moved_x = pos.0 + Vector2::new(step.x, 0.0);
+                if overlap(&map, moved_x, hitbox) > overlap(&map, pos.0, hitbox) {
+                    vel.0.x = 0.0;
+                } else {
+                    pos.0 = moved_x;
                 }
-                Down => {
-                    pos.0 = pos.0.offset(0, vel.speed);
+                let moved_y = pos.0 + Vector2::new(0.0, step.y);
+                if overlap(&map, moved_y, hitbox) > overlap(&map, pos.0, hitbox) {
+                    vel.0.y = 0.0;
+                } else {
+                    pos.0 = moved_y;
                 }
             }
         }
@@ -0,0 +1,284 @@
+use sdl2::rect::{Point, Rect};
+use serde::Deserialize;
+use specs::prelude::*;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::ai;
+use crate::components::*;
+use crate::config::{Config, HeroControl};
+use crate::sprite::{self, Spritesheets, Tint};
+use crate::vector::Vector2;
+
+/// The prefab the hero is created from
+pub const HERO_PREFAB: &str = "hero";
+
+/// Where a prefab's animation frames are in its spritesheet
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpriteLayout {
+    /// A standard character spritesheet (rows of 3 down, left, right and up frames)
+    pub spritesheet: PathBuf,
+    /// A colour to tint the spritesheet with, so prefabs sharing one can be told apart
+    pub tint: Option<Tint>,
+    /// The top left corner of the first frame, for spritesheets holding several characters
+    #[serde(default)]
+    pub x: i32,
+    #[serde(default)]
+    pub y: i32,
+    pub frame_width: u32,
+    pub frame_height: u32,
+    /// The spritesheet's number, once its prefab is resolved
+    #[serde(skip)]
+    pub index: usize,
+}
+
+/// A description of an entity's components, which can be based on another prefab and overridden
+/// when instantiated. Anything left out is taken from the base prefab, or not added at all.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Prefab {
+    /// The name of the prefab this one overrides
+    pub base: Option<String>,
+    pub hero: Option<bool>,
+    pub enemy: Option<bool>,
+    /// Who moves the entity
+    pub control: Option<HeroControl>,
+    /// The behaviour model of an enemy, which also gives it perception of the hero
+    pub role: Option<EnemyRole>,
+    /// Top speed, or None for the behaviour model's usual speed
+    pub speed: Option<f32>,
+    pub position: Option<(f32, f32)>,
+    /// Starting velocity. Anything with a control always has a velocity.
+    pub velocity: Option<(f32, f32)>,
+    pub sprite: Option<SpriteLayout>,
+    /// The region of a frame (x, y, width and height, from its top left corner) which touches
+    /// other entities, defaulting to the whole frame. Without a sprite, it's centred on the
+    /// entity.
+    pub hitbox: Option<(i32, i32, u32, u32)>,
+    pub health: Option<u32>,
+    /// How much health the hero loses when touched
+    pub damage: Option<u32>,
+    /// Points scored for catching it
+    pub score: Option<u32>,
+    /// Record telemetry, shown in the HUD
+    pub telemetry: Option<bool>,
+}
+
+impl Prefab {
+    /// Overrides placing a prefab at a position
+    pub fn at(position: Vector2) -> Prefab {
+        Prefab {
+            position: Some((position.x, position.y)),
+            ..Prefab::default()
+        }
+    }
+
+    /// This prefab with anything set in `overrides` replaced
+    pub fn merged(&self, overrides: &Prefab) -> Prefab {
+        Prefab {
+            base: overrides.base.clone().or_else(|| self.base.clone()),
+            hero: overrides.hero.or(self.hero),
+            enemy: overrides.enemy.or(self.enemy),
+            control: overrides.control.or(self.control),
+            role: overrides.role.or(self.role),
+            speed: overrides.speed.or(self.speed),
+            position: overrides.position.or(self.position),
+            velocity: overrides.velocity.or(self.velocity),
+            sprite: overrides.sprite.clone().or_else(|| self.sprite.clone()),
+            hitbox: overrides.hitbox.or(self.hitbox),
+            health: overrides.health.or(self.health),
+            damage: overrides.damage.or(self.damage),
+            score: overrides.score.or(self.score),
+            telemetry: overrides.telemetry.or(self.telemetry),
+        }
+    }
+
+    /// The hitbox an entity made from this prefab has, relative to its position
+    pub fn hitbox_rect(&self) -> Option<Rect> {
+        // Frames are drawn centred on the position, so hitboxes are moved from the frame's top
+        // left corner to its centre
+        match (self.hitbox, &self.sprite) {
+            (Some((x, y, width, height)), Some(layout)) => Some(Rect::new(
+                x - layout.frame_width as i32 / 2,
+                y - layout.frame_height as i32 / 2,
+                width,
+                height,
+            )),
+            (Some((_, _, width, height)), None) => {
+                Some(Rect::from_center(Point::new(0, 0), width, height))
+            }
+            (None, Some(layout)) => Some(Rect::from_center(
+                Point::new(0, 0),
+                layout.frame_width,
+                layout.frame_height,
+            )),
+            (None, None) => None,
+        }
+    }
+
+    /// Add the prefab's components to an entity, with any which depend on the settings (like
+    /// steering and perception) set up from `config`
+    pub fn build<B: Builder>(&self, builder: B, config: &Config) -> Entity {
+        let mut builder = builder;
+        let hero = self.hero == Some(true);
+        if hero {
+            builder = builder.with(Hero);
+        }
+        if self.enemy == Some(true) {
+            builder = builder.with(Enemy);
+        }
+        builder = match self.control {
+            Some(HeroControl::AI) => builder.with(AIControlled),
+            Some(HeroControl::Keyboard) => builder.with(KeyboardControlled),
+            None => builder,
+        };
+
+        let steering = match (self.role, hero) {
+            (Some(role), _) => Some(ai::enemy_steering(role, config.flocking)),
+            (None, true) => Some(ai::hero_steering(config.mode)),
+            (None, false) => None,
+        };
+        if let Some(mut steering) = steering {
+            if let Some(speed) = self.speed {
+                steering.max_speed = speed;
+            }
+            builder = builder.with(steering);
+        }
+        if let Some(role) = self.role {
+            builder = builder.with(role).with(Perception {
+                radius: config.perception_radius,
+                line_of_sight: config.line_of_sight,
+            });
+        }
+
+        if let Some((x, y)) = self.position {
+            builder = builder.with(Position(Vector2::new(x, y)));
+        }
+        match self.velocity {
+            Some((x, y)) => builder = builder.with(Velocity(Vector2::new(x, y))),
+            None if self.control.is_some() => builder = builder.with(Velocity::default()),
+            None => {}
+        }
+
+        if let Some(layout) = &self.sprite {
+            let top_left_frame =
+                Rect::new(layout.x, layout.y, layout.frame_width, layout.frame_height);
+            let animation = sprite::character_animation(layout.index, top_left_frame);
+            builder = builder
+                .with(animation.right_frames[0].clone())
+                .with(animation);
+        }
+        if let Some(hitbox) = self.hitbox_rect() {
+            builder = builder.with(Hitbox(hitbox));
+        }
+
+        if let Some(health) = self.health {
+            builder = builder.with(Health {
+                current: health,
+                max: health,
+            });
+        }
+        if let Some(damage) = self.damage {
+            builder = builder.with(Damage(damage));
+        }
+        if let Some(score) = self.score {
+            builder = builder.with(Score(score));
+        }
+        if self.telemetry == Some(true) {
+            builder = builder.with(Telemetry::default());
+        }
+        builder.build()
+    }
+
+    /// Create an entity from the prefab straight away
+    pub fn instantiate(&self, world: &mut World) -> Entity {
+        let config = (*world.read_resource::<Config>()).clone();
+        self.build(world.create_entity(), &config)
+    }
+
+    /// Create an entity from the prefab from inside a system, once the world is next maintained
+    pub fn instantiate_lazy(
+        &self,
+        lazy: &LazyUpdate,
+        entities: &Entities,
+        config: &Config,
+    ) -> Entity {
+        self.build(lazy.create_entity(entities), config)
+    }
+}
+
+/// Prefabs by name, each already merged with its base
+#[derive(Debug, Clone, Default)]
+pub struct Prefabs {
+    prefabs: HashMap<String, Prefab>,
+}
+
+impl Prefabs {
+    /// Load named prefabs from a TOML file, numbering their spritesheets
+    pub fn load(path: &Path, spritesheets: &mut Spritesheets) -> Result<Prefabs, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let unresolved: HashMap<String, Prefab> =
+            toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        let mut prefabs = Prefabs::default();
+        for name in unresolved.keys() {
+            prefabs.define(name, &unresolved, spritesheets, &mut Vec::new())?;
+        }
+        Ok(prefabs)
+    }
+
+    /// Resolve a prefab, after its base (and its base's base, ...)
+    fn define(
+        &mut self,
+        name: &str,
+        unresolved: &HashMap<String, Prefab>,
+        spritesheets: &mut Spritesheets,
+        bases: &mut Vec<String>,
+    ) -> Result<(), String> {
+        if self.prefabs.contains_key(name) {
+            return Ok(());
+        }
+        if bases.iter().any(|base| base == name) {
+            return Err(format!("Prefab {} is based on itself", name));
+        }
+        let prefab = unresolved
+            .get(name)
+            .ok_or(format!("No prefab called {}", name))?;
+        if let Some(base) = &prefab.base {
+            bases.push(name.to_string());
+            self.define(base, unresolved, spritesheets, bases)?;
+            bases.pop();
+        }
+        let resolved = self.resolve(prefab, spritesheets)?;
+        self.prefabs.insert(name.to_string(), resolved);
+        Ok(())
+    }
+
+    /// Merge a prefab with its base (if it has one), numbering its spritesheet
+    pub fn resolve(
+        &self,
+        prefab: &Prefab,
+        spritesheets: &mut Spritesheets,
+    ) -> Result<Prefab, String> {
+        let mut resolved = match &prefab.base {
+            Some(base) => self.get(base)?.merged(prefab),
+            None => prefab.clone(),
+        };
+        resolved.base = None;
+        if let Some(layout) = &mut resolved.sprite {
+            layout.index = spritesheets.index(&layout.spritesheet, layout.tint);
+        }
+        Ok(resolved)
+    }
+
+    pub fn all(&self) -> impl Iterator<Item = &Prefab> {
+        self.prefabs.values()
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Prefab, String> {
+        self.prefabs
+            .get(name)
+            .ok_or(format!("No prefab called {}", name))
+    }
+}
@@ -1,69 +1,445 @@
-use sdl2::pixels::Color;
+use sdl2::image::LoadTexture;
+use sdl2::pixels::{Color, PixelFormatEnum};
 use sdl2::rect::{Point, Rect};
-use sdl2::render::{Texture, TextureCreator, TextureQuery, WindowCanvas};
+use sdl2::render::{BlendMode, Texture, TextureCreator, TextureQuery, WindowCanvas};
+use sdl2::surface::Surface;
 use sdl2::ttf::Font;
 use sdl2::video::WindowContext;
 use specs::prelude::*;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
+use crate::camera::Camera;
 use crate::components::*;
+use crate::config::Config;
+use crate::enemy_collider_purger;
+use crate::map::TileMap;
+use crate::sprite::Spritesheets;
+use crate::vector::Vector2;
+
+/// How many ticks the hero spends visible (then hidden) while flashing after a hit
+const INVULNERABLE_FLASH_TICKS: u32 = 4;
+/// How many ticks of movement the debug velocity arrows are drawn for
+const VELOCITY_ARROW_TICKS: f32 = 5.0;
+const ARROW_HEAD_LENGTH: f32 = 6.0;
+/// The widest text is allowed to be before it wraps
+const TEXT_WRAP_WIDTH: u32 = 800;
 
 // Type alias for the data needed by the renderer
 pub type SystemData<'a> = (
+    ReadExpect<'a, Camera>,
+    Read<'a, TileMap>,
     ReadStorage<'a, Position>,
     ReadStorage<'a, Sprite>,
     ReadStorage<'a, Telemetry>,
+    ReadStorage<'a, Obstacle>,
+    ReadStorage<'a, Health>,
+    ReadStorage<'a, Invulnerable>,
+    ReadStorage<'a, Selected>,
+    DebugData<'a>,
+);
+
+/// The extra data needed to draw the debug overlay
+type DebugData<'a> = (
+    Entities<'a>,
+    ReadExpect<'a, Config>,
+    ReadStorage<'a, Velocity>,
+    ReadStorage<'a, Steering>,
+    ReadStorage<'a, Hero>,
+    ReadStorage<'a, Hitbox>,
 );
 
+/// Every image the renderer draws from
+pub struct Textures<'a> {
+    /// Spritesheets, indexed by `Sprite::spritesheet`
+    pub sprites: Vec<Texture<'a>>,
+    /// Map tileset images, by path
+    pub tilesets: HashMap<PathBuf, Texture<'a>>,
+}
+
+impl<'a> Textures<'a> {
+    pub fn new() -> Textures<'a> {
+        Textures {
+            sprites: Vec::new(),
+            tilesets: HashMap::new(),
+        }
+    }
+
+    /// Load (and tint) any spritesheets which haven't been loaded yet
+    pub fn load_spritesheets(
+        &mut self,
+        texture_creator: &'a TextureCreator<WindowContext>,
+        spritesheets: &Spritesheets,
+    ) -> Result<(), String> {
+        for (path, tint) in spritesheets.sheets.iter().skip(self.sprites.len()) {
+            let mut texture = texture_creator.load_texture(path)?;
+            if let Some((r, g, b)) = *tint {
+                texture.set_color_mod(r, g, b);
+            }
+            self.sprites.push(texture);
+        }
+        Ok(())
+    }
+
+    /// Load any tileset images used by the map which haven't been loaded yet
+    pub fn load_tilesets(
+        &mut self,
+        texture_creator: &'a TextureCreator<WindowContext>,
+        map: &TileMap,
+    ) -> Result<(), String> {
+        for tileset in &map.tilesets {
+            if !self.tilesets.contains_key(&tileset.image) {
+                let texture = texture_creator.load_texture(&tileset.image)?;
+                self.tilesets.insert(tileset.image.clone(), texture);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Text drawn over the world that doesn't come from its components
+pub struct Hud<'a> {
+    /// How the simulation is being run, shown next to the telemetry
+    pub clock_status: &'a str,
+    /// The current screen's text, shown over a darkened world
+    pub overlay: Option<&'a str>,
+    /// The components of the selected entity, shown in a side panel
+    pub inspector: Option<&'a str>,
+    /// Whether to draw the debug overlay over the world
+    pub debug: bool,
+    /// The developer console's text, if it's open
+    pub console: Option<&'a str>,
+}
+
+/// Render (possibly multi-line) text into a texture
+fn text_texture<'t>(
+    texture_creator: &'t TextureCreator<WindowContext>,
+    font: &Font,
+    text: &str,
+    color: Color,
+) -> Result<Texture<'t>, String> {
+    let surface = font
+        .render(text)
+        .blended_wrapped(color, TEXT_WRAP_WIDTH)
+        .map_err(|e| e.to_string())?;
+    texture_creator
+        .create_texture_from_surface(&surface)
+        .map_err(|e| e.to_string())
+}
+
+/// Fill a circle, one horizontal line per row
+fn fill_circle(canvas: &mut WindowCanvas, center: Point, radius: i32) -> Result<(), String> {
+    for dy in -radius..=radius {
+        let dx = ((radius * radius - dy * dy) as f32).sqrt().round() as i32;
+        canvas.draw_line(
+            Point::new(center.x - dx, center.y + dy),
+            Point::new(center.x + dx, center.y + dy),
+        )?;
+    }
+    Ok(())
+}
+
+/// The size of the area being rendered to, which is the logical resolution if there is one
+pub fn viewport(canvas: &WindowCanvas) -> Result<(u32, u32), String> {
+    match canvas.logical_size() {
+        (0, 0) => canvas.output_size(),
+        size => Ok(size),
+    }
+}
+
 pub fn render(
     canvas: &mut WindowCanvas,
     background: Color,
-    textures: &[Texture],
+    textures: &Textures,
     texture_creator: &TextureCreator<WindowContext>,
     font: &Font,
-    (positions, sprites, telemetries): SystemData,
+    hud: &Hud,
+    (
+        camera,
+        map,
+        positions,
+        sprites,
+        telemetries,
+        obstacles,
+        healths,
+        invulnerables,
+        selected,
+        debug_data,
+    ): SystemData,
 ) -> Result<(), String> {
     canvas.set_draw_color(background);
     canvas.clear();
 
-    let (width, height) = canvas.output_size()?;
+    let (width, height) = viewport(canvas)?;
+    let screen_center = Point::new(width as i32 / 2, height as i32 / 2);
+
+    render_map(canvas, textures, &camera, &map)?;
+
+    canvas.set_draw_color(Color::RGB(64, 64, 64));
+    for (pos, obstacle) in (&positions, &obstacles).join() {
+        let radius = camera.scale(obstacle.radius.round() as u32) as i32;
+        fill_circle(canvas, camera.to_screen(pos.0), radius)?;
+    }
+
+    for (pos, sprite, invulnerable) in (&positions, &sprites, invulnerables.maybe()).join() {
+        // Flash while invulnerable
+        if let Some(invulnerable) = invulnerable {
+            if invulnerable.ticks_remaining / INVULNERABLE_FLASH_TICKS % 2 == 1 {
+                continue;
+            }
+        }
 
-    for (pos, sprite) in (&positions, &sprites).join() {
         let current_frame = sprite.region;
 
-        // Treat the center of the screen as the (0, 0) coordinate
-        let screen_position = pos.0 + Point::new(width as i32 / 2, height as i32 / 2);
-        let screen_rect = Rect::from_center(
-            screen_position,
-            current_frame.width(),
-            current_frame.height(),
+        let screen_rect =
+            camera.to_screen_rect_centered(pos.0, current_frame.width(), current_frame.height());
+        canvas.copy(
+            &textures.sprites[sprite.spritesheet],
+            current_frame,
+            screen_rect,
+        )?;
+    }
+
+    // Outline whatever has been selected with the mouse
+    canvas.set_draw_color(Color::RGB(255, 255, 0));
+    for (pos, sprite, obstacle, _) in
+        (&positions, sprites.maybe(), obstacles.maybe(), &selected).join()
+    {
+        let (width, height) = match (sprite, obstacle) {
+            (Some(sprite), _) => (sprite.region.width(), sprite.region.height()),
+            (None, Some(obstacle)) => {
+                let diameter = (obstacle.radius * 2.0) as u32;
+                (diameter, diameter)
+            }
+            (None, None) => continue,
+        };
+        let screen_rect = camera.to_screen_rect_centered(pos.0, width, height);
+        let outline = Rect::from_center(
+            screen_rect.center(),
+            screen_rect.width() + 4,
+            screen_rect.height() + 4,
         );
-        canvas.copy(&textures[sprite.spritesheet], current_frame, screen_rect)?;
+        canvas.draw_rect(outline)?;
+    }
+
+    if hud.debug {
+        render_debug(
+            canvas,
+            texture_creator,
+            font,
+            &camera,
+            &positions,
+            debug_data,
+        )?;
     }
 
     // Render Telemetry Info
-    match (&telemetries).join().last() {
-        Some(telemetry) => {
-            let text = format!(
-                "Spawned: {}\nOOB: {}\nCollided: {}",
-                telemetry.enemy_spawned, telemetry.enemy_oob, telemetry.enemy_collisions
-            );
-            let surface = font
-                .render(&text)
-                .blended_wrapped(Color::RGBA(0, 0, 0, 255), super::WORLD_WIDTH)
-                .map_err(|e| e.to_string())?;
-            let texture = texture_creator
-                .create_texture_from_surface(&surface)
-                .map_err(|e| e.to_string())?;
+    // The hero carries the telemetry
+    match (&telemetries, healths.maybe()).join().last() {
+        Some((telemetry, health)) => {
+            let mut text = telemetry.summary();
+            if let Some(health) = health {
+                text = format!("Health: {}/{}\n{}", health.current, health.max, text);
+            }
+            let texture = text_texture(texture_creator, font, &text, Color::RGBA(0, 0, 0, 255))?;
             let TextureQuery { width, height, .. } = texture.query();
 
             let padding = 10;
-            let target = super::rect!(padding, padding, width + padding, height + padding);
+            let target = super::rect!(padding, padding, width, height);
             canvas.copy(&texture, None, Some(target))?;
+
+            // Show how the simulation is being run alongside the telemetry
+            let status_texture = text_texture(
+                texture_creator,
+                font,
+                hud.clock_status,
+                Color::RGBA(0, 0, 0, 255),
+            )?;
+            let TextureQuery {
+                width: status_width,
+                height: status_height,
+                ..
+            } = status_texture.query();
+            let target = super::rect!(padding * 3 + width, padding, status_width, status_height);
+            canvas.copy(&status_texture, None, Some(target))?;
         }
         None => eprintln!("Telemetry Missing"),
     }
 
-    canvas.present();
+    // Render the current screen's text over a darkened world
+    if let Some(overlay) = hud.overlay {
+        canvas.set_blend_mode(BlendMode::Blend);
+        canvas.set_draw_color(Color::RGBA(0, 0, 0, 160));
+        canvas.fill_rect(None)?;
+        canvas.set_blend_mode(BlendMode::None);
+
+        let texture = text_texture(texture_creator, font, overlay, Color::RGB(255, 255, 255))?;
+        let TextureQuery { width, height, .. } = texture.query();
+        let target = Rect::from_center(screen_center, width, height);
+        canvas.copy(&texture, None, Some(target))?;
+    }
 
+    // Render the inspector panel down the right hand side, over everything else
+    if let Some(inspector) = hud.inspector {
+        let texture = text_texture(texture_creator, font, inspector, Color::RGB(255, 255, 255))?;
+        let TextureQuery {
+            width: text_width,
+            height: text_height,
+            ..
+        } = texture.query();
+        let padding = 10;
+        let panel = super::rect!(
+            width.saturating_sub(text_width + padding * 3),
+            padding,
+            text_width + padding * 2,
+            text_height + padding * 2
+        );
+        canvas.set_blend_mode(BlendMode::Blend);
+        canvas.set_draw_color(Color::RGBA(0, 0, 0, 200));
+        canvas.fill_rect(panel)?;
+        canvas.set_blend_mode(BlendMode::None);
+        let target = super::rect!(
+            panel.x() + padding as i32,
+            panel.y() + padding as i32,
+            text_width,
+            text_height
+        );
+        canvas.copy(&texture, None, Some(target))?;
+    }
+
+    // Render the console dropped down from the top, over everything else
+    if let Some(console) = hud.console {
+        let texture = text_texture(texture_creator, font, console, Color::RGB(0, 255, 0))?;
+        let TextureQuery {
+            width: text_width,
+            height: text_height,
+            ..
+        } = texture.query();
+        let padding = 10;
+        canvas.set_blend_mode(BlendMode::Blend);
+        canvas.set_draw_color(Color::RGBA(0, 0, 0, 220));
+        canvas.fill_rect(super::rect!(0, 0, width, text_height + padding * 2))?;
+        canvas.set_blend_mode(BlendMode::None);
+        let target = super::rect!(padding, padding, text_width, text_height);
+        canvas.copy(&texture, None, Some(target))?;
+    }
+
+    Ok(())
+}
+
+/// Draw the map's visible tile layers, bottom layer first
+fn render_map(
+    canvas: &mut WindowCanvas,
+    textures: &Textures,
+    camera: &Camera,
+    map: &TileMap,
+) -> Result<(), String> {
+    if map.layers.is_empty() {
+        return Ok(());
+    }
+
+    // Only draw the tiles which are on screen
+    let (width, height) = camera.viewport;
+    let clamp_to_map = |position: Vector2| {
+        let offset = position - map.origin();
+        let column = (offset.x / map.tile_width as f32).floor();
+        let row = (offset.y / map.tile_height as f32).floor();
+        (
+            column.clamp(0.0, map.columns as f32 - 1.0) as u32,
+            row.clamp(0.0, map.rows as f32 - 1.0) as u32,
+        )
+    };
+    let (first_column, first_row) = clamp_to_map(camera.to_world(0, 0));
+    let (last_column, last_row) = clamp_to_map(camera.to_world(width as i32, height as i32));
+
+    for layer in map.layers.iter().filter(|layer| layer.visible) {
+        for row in first_row..=last_row {
+            for column in first_column..=last_column {
+                let gid = layer.tiles[(row * map.columns + column) as usize];
+                if gid == 0 {
+                    continue;
+                }
+                let (tileset, region) = match map.tile_source(gid) {
+                    Some(source) => source,
+                    None => continue,
+                };
+                if let Some(texture) = textures.tilesets.get(&tileset.image) {
+                    let screen_rect = camera.to_screen_rect(map.tile_rect(column, row));
+                    canvas.copy(texture, region, screen_rect)?;
+                }
+            }
+        }
+    }
     Ok(())
 }
+
+/// Draw what the simulation sees: the bounds of the arena, the rectangles heroes
+/// and enemies collide over, the targets heroes are steering for, velocities and entity IDs
+fn render_debug(
+    canvas: &mut WindowCanvas,
+    texture_creator: &TextureCreator<WindowContext>,
+    font: &Font,
+    camera: &Camera,
+    positions: &ReadStorage<Position>,
+    (entities, config, velocities, steerings, heroes, hitboxes): DebugData,
+) -> Result<(), String> {
+    let to_screen = |v: Vector2| camera.to_screen(v);
+
+    let (min, max) = config.world_bounds();
+    canvas.set_draw_color(Color::RGB(255, 0, 255));
+    canvas.draw_rect(camera.to_screen_rect(super::rect!(
+        min.x,
+        min.y,
+        max.x - min.x,
+        max.y - min.y
+    )))?;
+
+    canvas.set_draw_color(Color::RGB(255, 0, 0));
+    for (pos, hitbox) in (positions, &hitboxes).join() {
+        let hitbox = enemy_collider_purger::hitbox_rect(pos, hitbox);
+        canvas.draw_rect(camera.to_screen_rect(hitbox))?;
+    }
+
+    canvas.set_draw_color(Color::RGB(255, 255, 0));
+    for (pos, steer, _) in (positions, &steerings, &heroes).join() {
+        for &(_, target) in &steer.targets {
+            if let Some(target_pos) = positions.get(target) {
+                canvas.draw_line(to_screen(pos.0), to_screen(target_pos.0))?;
+            }
+        }
+    }
+
+    canvas.set_draw_color(Color::RGB(0, 255, 0));
+    for (pos, vel) in (positions, &velocities).join() {
+        if vel.0.is_zero() {
+            continue;
+        }
+        let tip = pos.0 + vel.0 * VELOCITY_ARROW_TICKS;
+        canvas.draw_line(to_screen(pos.0), to_screen(tip))?;
+        for side in &[-1.0, 1.0] {
+            let barb = vel.0.angle() + std::f32::consts::PI + side * std::f32::consts::FRAC_PI_6;
+            let barb_end = tip + Vector2::from_angle(barb) * ARROW_HEAD_LENGTH;
+            canvas.draw_line(to_screen(tip), to_screen(barb_end))?;
+        }
+    }
+
+    for (entity, pos) in (&entities, positions).join() {
+        let label = entity.id().to_string();
+        let texture = text_texture(texture_creator, font, &label, Color::RGB(255, 255, 255))?;
+        let TextureQuery { width, height, .. } = texture.query();
+        let target = Rect::from_center(to_screen(pos.0), width, height);
+        canvas.copy(&texture, None, Some(target))?;
+    }
+
+    Ok(())
+}
+
+/// Save what has been rendered (but not yet presented) to a BMP image
+pub fn save_screenshot(canvas: &WindowCanvas, path: &Path) -> Result<(), String> {
+    let (width, height) = canvas.output_size()?;
+    let format = PixelFormatEnum::ARGB8888;
+    let mut pixels = canvas.read_pixels(None, format)?;
+    let pitch = format.byte_size_per_pixel() as u32 * width;
+    let surface = Surface::from_data(&mut pixels, width, height, pitch, format)?;
+    surface.save_bmp(path)
+}
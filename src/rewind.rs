@@ -0,0 +1,88 @@
+use specs::prelude::*;
+use std::collections::VecDeque;
+
+use crate::clock::TICKS_PER_SECOND;
+use crate::snapshot::Snapshot;
+
+/// A move through the recorded history of the world
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RewindControl {
+    /// Go back to the tick before the one shown
+    Back,
+    /// Go forward to the tick after the one shown
+    Forward,
+}
+
+/// Snapshots of the world after each of the last few seconds of ticks, which can be scrubbed
+/// through while paused. Snapshots share the settings, map and spawn rules while they're
+/// unchanged, so each tick only costs a copy of the entities.
+pub struct History {
+    snapshots: VecDeque<Snapshot>,
+    capacity: usize,
+    /// The snapshot the world was last rewound (or moved forward) to, while scrubbing
+    cursor: Option<usize>,
+}
+
+impl History {
+    pub fn new(seconds: f32) -> History {
+        let capacity = (seconds.max(0.0) * TICKS_PER_SECOND as f32).round() as usize;
+        History {
+            snapshots: VecDeque::with_capacity(capacity),
+            capacity,
+            cursor: None,
+        }
+    }
+
+    /// Forget everything recorded, e.g. when the world is replaced
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+        self.cursor = None;
+    }
+
+    /// Record the world after a tick. Carrying on from a rewound tick forgets the ticks which
+    /// followed it, as they won't happen now.
+    pub fn record(&mut self, world: &World) {
+        if self.capacity == 0 {
+            return;
+        }
+        if let Some(cursor) = self.cursor.take() {
+            self.snapshots.truncate(cursor + 1);
+        }
+        let snapshot = Snapshot::capture_after(world, self.snapshots.back());
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    /// Replace the world with the recorded tick before (or after) the one shown. Returns
+    /// whether there was one.
+    pub fn apply(&mut self, control: RewindControl, world: &mut World) -> bool {
+        let last = match self.snapshots.len() {
+            0 => return false,
+            len => len - 1,
+        };
+        let current = self.cursor.unwrap_or(last);
+        let target = match control {
+            RewindControl::Back => current.saturating_sub(1),
+            RewindControl::Forward => (current + 1).min(last),
+        };
+        if target == current {
+            return false;
+        }
+        self.snapshots[target].clone().restore(world);
+        self.cursor = Some(target);
+        true
+    }
+
+    /// How far back the world has been rewound, if it has
+    pub fn status(&self) -> Option<String> {
+        let cursor = self.cursor?;
+        let ticks_back = self.snapshots.len() - 1 - cursor;
+        Some(format!(
+            "Rewound: {:.2}s ({} ticks)",
+            ticks_back as f32 / TICKS_PER_SECOND as f32,
+            ticks_back
+        ))
+    }
+}
@@ -0,0 +1,338 @@
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::camera::Camera;
+use crate::components::*;
+use crate::config::Config;
+use crate::map::TileMap;
+use crate::spawn_rules::{SpawnRules, WaveProgress};
+use crate::sprite::Spritesheets;
+use crate::vector::Vector2;
+use crate::{GameOver, GameRng};
+
+/// Where quick saves are saved to, and quick loads loaded from
+pub const QUICKSAVE_PATH: &str = "quicksave.json";
+/// Bumped whenever the format changes, so old snapshots are rejected rather than misread
+const SNAPSHOT_VERSION: u32 = 2;
+
+/// Every saved component of an entity
+#[derive(Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct EntitySnapshot {
+    /// The entity captured, for pointing the steering targets of snapshots kept in memory at
+    /// the entities recreated from them
+    #[serde(skip)]
+    entity: Option<Entity>,
+    hero: bool,
+    enemy: bool,
+    ai_controlled: bool,
+    keyboard_controlled: bool,
+    selected: bool,
+    against_edge: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    position: Option<Position>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    velocity: Option<Velocity>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    steering: Option<Steering>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    role: Option<EnemyRole>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    perception: Option<Perception>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    health: Option<Health>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    damage: Option<Damage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    invulnerable: Option<Invulnerable>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hitbox: Option<Hitbox>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    score: Option<Score>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    obstacle: Option<Obstacle>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    telemetry: Option<Telemetry>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sprite: Option<Sprite>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    animation: Option<MovementAnimation>,
+}
+
+fn component<T: Component + Clone>(world: &World, entity: Entity) -> Option<T> {
+    world.read_storage::<T>().get(entity).cloned()
+}
+
+fn with<B: Builder, T: Component + Send + Sync>(builder: B, component: Option<T>) -> B {
+    match component {
+        Some(component) => builder.with(component),
+        None => builder,
+    }
+}
+
+fn with_tag<B: Builder, T: Component + Send + Sync + Default>(builder: B, tagged: bool) -> B {
+    with(builder, if tagged { Some(T::default()) } else { None })
+}
+
+impl EntitySnapshot {
+    fn capture(world: &World, entity: Entity) -> EntitySnapshot {
+        EntitySnapshot {
+            entity: Some(entity),
+            hero: world.read_storage::<Hero>().contains(entity),
+            enemy: world.read_storage::<Enemy>().contains(entity),
+            ai_controlled: world.read_storage::<AIControlled>().contains(entity),
+            keyboard_controlled: world.read_storage::<KeyboardControlled>().contains(entity),
+            selected: world.read_storage::<Selected>().contains(entity),
+            against_edge: world.read_storage::<AgainstEdge>().contains(entity),
+            position: component(world, entity),
+            velocity: component(world, entity),
+            steering: component(world, entity),
+            role: component(world, entity),
+            perception: component(world, entity),
+            health: component(world, entity),
+            damage: component(world, entity),
+            invulnerable: component(world, entity),
+            hitbox: component(world, entity),
+            score: component(world, entity),
+            obstacle: component(world, entity),
+            telemetry: component(world, entity),
+            sprite: component(world, entity),
+            animation: component(world, entity),
+        }
+    }
+
+    /// The spritesheet numbers the entity's sprite and animation frames use
+    fn spritesheets(&self) -> impl Iterator<Item = usize> + '_ {
+        let frames = self.animation.iter().flat_map(|animation| {
+            animation
+                .up_frames
+                .iter()
+                .chain(&animation.down_frames)
+                .chain(&animation.left_frames)
+                .chain(&animation.right_frames)
+        });
+        self.sprite
+            .iter()
+            .chain(frames)
+            .map(|sprite| sprite.spritesheet)
+    }
+
+    /// Renumber the entity's spritesheets, from the numbering in the snapshot to the world's
+    fn renumber_spritesheets(&mut self, numbers: &[usize]) {
+        let frames = self.animation.iter_mut().flat_map(|animation| {
+            animation
+                .up_frames
+                .iter_mut()
+                .chain(&mut animation.down_frames)
+                .chain(&mut animation.left_frames)
+                .chain(&mut animation.right_frames)
+        });
+        for sprite in self.sprite.iter_mut().chain(frames) {
+            sprite.spritesheet = numbers[sprite.spritesheet];
+        }
+    }
+
+    fn restore(self, world: &mut World) -> Entity {
+        let builder = world.create_entity();
+        let builder = with_tag::<_, Hero>(builder, self.hero);
+        let builder = with_tag::<_, Enemy>(builder, self.enemy);
+        let builder = with_tag::<_, AIControlled>(builder, self.ai_controlled);
+        let builder = with_tag::<_, KeyboardControlled>(builder, self.keyboard_controlled);
+        let builder = with_tag::<_, Selected>(builder, self.selected);
+        let builder = with_tag::<_, AgainstEdge>(builder, self.against_edge);
+        let builder = with(builder, self.position);
+        let builder = with(builder, self.velocity);
+        let builder = with(builder, self.steering);
+        let builder = with(builder, self.role);
+        let builder = with(builder, self.perception);
+        let builder = with(builder, self.health);
+        let builder = with(builder, self.damage);
+        let builder = with(builder, self.invulnerable);
+        let builder = with(builder, self.hitbox);
+        let builder = with(builder, self.score);
+        let builder = with(builder, self.obstacle);
+        let builder = with(builder, self.telemetry);
+        let builder = with(builder, self.sprite);
+        let builder = with(builder, self.animation);
+        builder.build()
+    }
+}
+
+/// Where the camera was looking
+#[derive(Clone, Serialize, Deserialize)]
+struct CameraSnapshot {
+    position: Vector2,
+    zoom: f32,
+    /// Whether the camera was following the hero
+    following: bool,
+}
+
+/// A copy of a resource, shared with `previous` if that's a copy of the same thing
+fn shared<T: Clone + PartialEq>(resource: &T, previous: Option<&Arc<T>>) -> Arc<T> {
+    match previous {
+        Some(previous) if **previous == *resource => Arc::clone(previous),
+        _ => Arc::new(resource.clone()),
+    }
+}
+
+/// Everything in a world needed to carry on the game from where it was saved: every entity,
+/// the settings it was played with, the map and where the random numbers had got to
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    version: u32,
+    /// The settings, map and spawn rules rarely change, so can be shared between snapshots
+    config: Arc<Config>,
+    map: Arc<TileMap>,
+    spawn_rules: Arc<SpawnRules>,
+    wave_progress: WaveProgress,
+    rng: GameRng,
+    game_over: bool,
+    camera: CameraSnapshot,
+    /// The spritesheets the entities' sprites are numbered by
+    spritesheets: Spritesheets,
+    entities: Vec<EntitySnapshot>,
+}
+
+impl Snapshot {
+    pub fn capture(world: &World) -> Snapshot {
+        Snapshot::capture_after(world, None)
+    }
+
+    /// Capture the world, sharing the settings, map and spawn rules with the previous snapshot
+    /// where they haven't changed since, so a run of snapshots only holds one copy of each
+    pub fn capture_after(world: &World, previous: Option<&Snapshot>) -> Snapshot {
+        let camera = world.read_resource::<Camera>();
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            config: shared(
+                &*world.read_resource::<Config>(),
+                previous.map(|previous| &previous.config),
+            ),
+            map: shared(
+                &*world.read_resource::<TileMap>(),
+                previous.map(|previous| &previous.map),
+            ),
+            spawn_rules: shared(
+                &*world.read_resource::<SpawnRules>(),
+                previous.map(|previous| &previous.spawn_rules),
+            ),
+            wave_progress: (*world.read_resource::<WaveProgress>()).clone(),
+            rng: (*world.read_resource::<GameRng>()).clone(),
+            game_over: world.read_resource::<GameOver>().0,
+            camera: CameraSnapshot {
+                position: camera.position,
+                zoom: camera.zoom,
+                following: camera.follow.is_some(),
+            },
+            spritesheets: (*world.read_resource::<Spritesheets>()).clone(),
+            entities: world
+                .entities()
+                .join()
+                .map(|entity| EntitySnapshot::capture(world, entity))
+                .collect(),
+        }
+    }
+
+    /// Replace everything in the world with the snapshot
+    pub fn restore(self, world: &mut World) {
+        world.maintain();
+        world.delete_all();
+
+        let numbers: Vec<usize> = {
+            let mut spritesheets = world.write_resource::<Spritesheets>();
+            self.spritesheets
+                .sheets
+                .iter()
+                .map(|(path, tint)| spritesheets.index(path, *tint))
+                .collect()
+        };
+        let mut recreated = HashMap::new();
+        for mut entity in self.entities {
+            entity.renumber_spritesheets(&numbers);
+            let captured = entity.entity;
+            let restored = entity.restore(world);
+            if let Some(captured) = captured {
+                recreated.insert(captured, restored);
+            }
+        }
+        for steering in (&mut world.write_storage::<Steering>()).join() {
+            steering.targets = steering
+                .targets
+                .iter()
+                .filter_map(|&(target, entity)| Some((target, *recreated.get(&entity)?)))
+                .collect();
+        }
+
+        world.insert(Arc::try_unwrap(self.config).unwrap_or_else(|config| (*config).clone()));
+        world.insert(Arc::try_unwrap(self.map).unwrap_or_else(|map| (*map).clone()));
+        world.insert(
+            Arc::try_unwrap(self.spawn_rules).unwrap_or_else(|spawn_rules| (*spawn_rules).clone()),
+        );
+        world.insert(self.wave_progress);
+        world.insert(self.rng);
+        world.insert(GameOver(self.game_over));
+        let hero = {
+            let (entities, heroes): (Entities, ReadStorage<Hero>) = world.system_data();
+            (&entities, &heroes).join().map(|(hero, _)| hero).next()
+        };
+        let mut camera = world.write_resource::<Camera>();
+        camera.position = self.camera.position;
+        camera.zoom = self.camera.zoom;
+        camera.follow = if self.camera.following { hero } else { None };
+    }
+
+    /// Check a snapshot from a file can be restored (and played), before anything in the world
+    /// is replaced
+    fn check(&self) -> Result<(), String> {
+        let count = self.spritesheets.sheets.len();
+        for entity in &self.entities {
+            if let Some(number) = entity.spritesheets().find(|&number| number >= count) {
+                return Err(format!(
+                    "Spritesheet {} is used, but there are only {}",
+                    number, count
+                ));
+            }
+        }
+        // Images are loaded once the snapshot is in play, when a missing one can't be handled
+        let spritesheets = self.spritesheets.sheets.iter().map(|(path, _)| path);
+        let tilesets = self.map.tilesets.iter().map(|tileset| &tileset.image);
+        if let Some(image) = spritesheets.chain(tilesets).find(|image| !image.is_file()) {
+            return Err(format!("{} can't be found", image.display()));
+        }
+        Ok(())
+    }
+
+    /// Save the world to a (JSON) file
+    pub fn save(world: &World, path: &Path) -> Result<(), String> {
+        let text =
+            serde_json::to_string_pretty(&Snapshot::capture(world)).map_err(|e| e.to_string())?;
+        fs::write(path, text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Replace everything in the world with the snapshot saved at `path`
+    pub fn load(world: &mut World, path: &Path) -> Result<(), String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let json: serde_json::Value =
+            serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        // Check the version first, as other versions may not parse at all
+        let version = json["version"].as_u64();
+        if version != Some(SNAPSHOT_VERSION.into()) {
+            return Err(format!(
+                "{} isn't a version {} snapshot",
+                path.display(),
+                SNAPSHOT_VERSION
+            ));
+        }
+        let snapshot: Snapshot =
+            serde_json::from_value(json).map_err(|e| format!("{}: {}", path.display(), e))?;
+        snapshot
+            .check()
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        snapshot.restore(world);
+        Ok(())
+    }
+}
@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::components::EnemyRole;
+
+/// Where a wave's enemies appear
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpawnArea {
+    /// In the map's spawn zones, or around the center of the arena if it has none
+    Zones,
+    /// Just inside the edges of the arena (or the first open tile in from them)
+    Edges,
+    /// At one of the given points
+    Points(Vec<(f32, f32)>),
+}
+
+/// A group of enemies spawned one after another
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Wave {
+    /// Ticks to wait after the previous wave (or the start of the game) before this one begins
+    pub cooldown: u32,
+    /// How many enemies the wave spawns, or None to keep spawning forever
+    pub count: Option<u32>,
+    /// Only spawn kinds of enemy with this role, or None for any suiting the game mode
+    pub role: Option<EnemyRole>,
+    pub area: SpawnArea,
+    /// Ticks between spawns at the start of the wave
+    pub interval: u32,
+    /// Ticks between spawns by the end of the wave, changing steadily from `interval`. Only used
+    /// by waves with a count.
+    pub final_interval: Option<u32>,
+}
+
+impl Default for Wave {
+    fn default() -> Wave {
+        Wave {
+            cooldown: 0,
+            count: None,
+            role: None,
+            area: SpawnArea::Zones,
+            interval: 1,
+            final_interval: None,
+        }
+    }
+}
+
+impl Wave {
+    /// The ticks to wait after spawning the `spawned`th enemy of the wave
+    pub fn interval_after(&self, spawned: u32) -> u32 {
+        match (self.count, self.final_interval) {
+            (Some(count), Some(final_interval)) if count > 1 => {
+                let progress = spawned.min(count - 1) as f32 / (count - 1) as f32;
+                let interval = self.interval as f32
+                    + (final_interval as f32 - self.interval as f32) * progress;
+                interval.round() as u32
+            }
+            _ => self.interval,
+        }
+    }
+}
+
+/// When, where and what enemies spawn. The spawner never keeps more than `max_enemies` in play,
+/// pausing the current wave until there's room.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpawnRules {
+    /// Enemies never spawn closer than this to the hero, where there's room
+    pub min_hero_distance: f32,
+    /// Start again from the first wave after the last one
+    pub repeat: bool,
+    pub waves: Vec<Wave>,
+}
+
+/// One endless wave, spawning an enemy every tick
+impl Default for SpawnRules {
+    fn default() -> SpawnRules {
+        SpawnRules {
+            min_hero_distance: 0.0,
+            repeat: false,
+            waves: vec![Wave::default()],
+        }
+    }
+}
+
+impl SpawnRules {
+    /// Load spawn rules from a TOML file
+    pub fn load(path: &Path) -> Result<SpawnRules, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+/// How far through the spawn rules the current game is
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WaveProgress {
+    /// The index of the current wave, which may be past the last one
+    pub wave: usize,
+    /// Whether the current wave's cooldown has been started, by setting `wait` to it
+    pub cooldown_started: bool,
+    /// Enemies spawned so far in the current wave
+    pub spawned: u32,
+    /// Ticks until the next spawn (or the end of the cooldown)
+    pub wait: u32,
+}
@@ -1,40 +1,46 @@
 use crate::components::*;
 
 use sdl2::rect::Rect;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 
-const ENEMY_SPRITESHEET: usize = 1;
-pub const TEXTURE_PATHS: [&str; 2] = ["assets/bardo.png", "assets/reaper.png"];
-pub const ENEMY_FRAME_WIDTH: u32 = 32;
-pub const ENEMY_FRAME_HEIGHT: u32 = 36;
+/// A colour to multiply a spritesheet's colours by, as red, green and blue
+pub type Tint = (u8, u8, u8);
 
-pub const HERO_FRAME_WIDTH: u32 = 26;
-pub const HERO_FRAME_HEIGHT: u32 = 36;
+/// Every spritesheet used by prefabs, numbered by `Sprite::spritesheet`. The same image tinted
+/// differently is a different spritesheet.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Spritesheets {
+    pub sheets: Vec<(PathBuf, Option<Tint>)>,
+}
 
-pub fn enemy_animation() -> MovementAnimation {
-    let enemy_top_left_frame = Rect::new(0, 0, ENEMY_FRAME_WIDTH, ENEMY_FRAME_HEIGHT);
-    return MovementAnimation {
+impl Spritesheets {
+    /// The number of a spritesheet, adding it if it's new
+    pub fn index(&mut self, path: &Path, tint: Option<Tint>) -> usize {
+        match self
+            .sheets
+            .iter()
+            .position(|(p, t)| p == path && *t == tint)
+        {
+            Some(index) => index,
+            None => {
+                self.sheets.push((path.to_path_buf(), tint));
+                self.sheets.len() - 1
+            }
+        }
+    }
+}
+
+/// Walking animations for a character whose first frame is `top_left_frame`, in the standard
+/// character spritesheet layout
+pub fn character_animation(spritesheet: usize, top_left_frame: Rect) -> MovementAnimation {
+    MovementAnimation {
         current_frame: 0,
-        up_frames: character_animation_frames(
-            ENEMY_SPRITESHEET,
-            enemy_top_left_frame,
-            Direction::Up,
-        ),
-        down_frames: character_animation_frames(
-            ENEMY_SPRITESHEET,
-            enemy_top_left_frame,
-            Direction::Down,
-        ),
-        left_frames: character_animation_frames(
-            ENEMY_SPRITESHEET,
-            enemy_top_left_frame,
-            Direction::Left,
-        ),
-        right_frames: character_animation_frames(
-            ENEMY_SPRITESHEET,
-            enemy_top_left_frame,
-            Direction::Right,
-        ),
-    };
+        up_frames: character_animation_frames(spritesheet, top_left_frame, Direction::Up),
+        down_frames: character_animation_frames(spritesheet, top_left_frame, Direction::Down),
+        left_frames: character_animation_frames(spritesheet, top_left_frame, Direction::Left),
+        right_frames: character_animation_frames(spritesheet, top_left_frame, Direction::Right),
+    }
 }
 
 /// Returns the row of the spritesheet corresponding to the given direction
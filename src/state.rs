@@ -0,0 +1,261 @@
+use crate::camera::CameraControl;
+use crate::clock::ClockControl;
+use crate::components::Telemetry;
+use crate::config::{Config, GameMode, HeroControl};
+use crate::input::{Action, Bindings};
+use crate::inspector::InspectorEdit;
+use crate::rewind::RewindControl;
+
+/// The names of the settings which can be changed from the settings screen, in display order
+const SETTINGS: [&str; 5] = [
+    "Mode",
+    "Flocking",
+    "Line of sight",
+    "Hero health",
+    "Hero control",
+];
+const MAX_HERO_HEALTH: u32 = 10;
+
+/// A screen of the game, deciding what runs, what's shown and what actions mean
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameState {
+    Title,
+    Playing,
+    Paused,
+    GameOver,
+    Settings { selected: usize },
+}
+
+/// What should happen after a state handles an action
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    Stay,
+    /// Stay in the current state, adjusting how the simulation is dispatched
+    Clock(ClockControl),
+    /// Swap the hero between AI and keyboard control
+    ToggleHeroControl,
+    /// Edit the entity selected for inspection
+    Inspect(InspectorEdit),
+    Camera(CameraControl),
+    /// Save a snapshot of the world to the quicksave file
+    Save,
+    /// Replace the world with the quicksave snapshot
+    Load,
+    /// The world has been replaced with a snapshot, so play on from there, forgetting anything
+    /// about the old world
+    Loaded,
+    /// Replace the world with a recorded tick from its history
+    Rewind(RewindControl),
+    Push(GameState),
+    Pop,
+    /// Replace the whole stack with the given state
+    Reset(GameState),
+    /// Build a fresh world from the current settings and start playing it
+    NewGame,
+    Quit,
+}
+
+impl GameState {
+    /// Whether the world should be updated while this state is on top
+    pub fn runs_dispatcher(&self) -> bool {
+        matches!(self, GameState::Playing)
+    }
+
+    /// Whether the world can be edited with the mouse while this state is on top
+    pub fn edits_world(&self) -> bool {
+        matches!(self, GameState::Playing | GameState::Paused)
+    }
+
+    /// Interpret an action, possibly changing the settings used for the next game
+    pub fn handle_action(&mut self, action: Action, settings: &mut Config) -> Transition {
+        // The camera can be moved whenever the world can be edited
+        if let (true, Some(control)) = (self.edits_world(), action.camera_control()) {
+            return Transition::Camera(control);
+        }
+        match (self, action) {
+            (GameState::Title, Action::Confirm) => Transition::NewGame,
+            (GameState::Title, Action::Settings) => {
+                Transition::Push(GameState::Settings { selected: 0 })
+            }
+            (GameState::Title, Action::Pause | Action::Quit) => Transition::Quit,
+            (GameState::Playing | GameState::Paused, Action::QuickSave) => Transition::Save,
+            (GameState::Playing | GameState::Paused, Action::QuickLoad) => Transition::Load,
+            (GameState::Playing, Action::Pause) => Transition::Push(GameState::Paused),
+            (GameState::Playing, Action::ToggleControl) => Transition::ToggleHeroControl,
+            (GameState::Playing, Action::Freeze) => Transition::Clock(ClockControl::ToggleFreeze),
+            (GameState::Playing, Action::Step) => Transition::Clock(ClockControl::Step),
+            (GameState::Playing, Action::SlowDown) => Transition::Clock(ClockControl::SlowDown),
+            (GameState::Playing, Action::SpeedUp) => Transition::Clock(ClockControl::SpeedUp),
+            (GameState::Paused, Action::Pause) => Transition::Pop,
+            (GameState::Paused, Action::Quit) => Transition::Reset(GameState::Title),
+            (GameState::Paused, Action::RewindBack) => Transition::Rewind(RewindControl::Back),
+            (GameState::Paused, Action::RewindForward) => {
+                Transition::Rewind(RewindControl::Forward)
+            }
+            (GameState::Paused, Action::MoveUp) => Transition::Inspect(InspectorEdit::Faster),
+            (GameState::Paused, Action::MoveDown) => Transition::Inspect(InspectorEdit::Slower),
+            (GameState::Paused, Action::MoveLeft) => Transition::Inspect(InspectorEdit::TurnLeft),
+            (GameState::Paused, Action::MoveRight) => Transition::Inspect(InspectorEdit::TurnRight),
+            (GameState::GameOver, Action::Confirm) => Transition::NewGame,
+            (GameState::GameOver, Action::Pause | Action::Quit) => {
+                Transition::Reset(GameState::Title)
+            }
+            (GameState::Settings { .. }, Action::Pause) => Transition::Pop,
+            (GameState::Settings { selected }, Action::MoveUp) => {
+                *selected = (*selected + SETTINGS.len() - 1) % SETTINGS.len();
+                Transition::Stay
+            }
+            (GameState::Settings { selected }, Action::MoveDown) => {
+                *selected = (*selected + 1) % SETTINGS.len();
+                Transition::Stay
+            }
+            (GameState::Settings { selected }, Action::MoveLeft) => {
+                adjust_setting(settings, *selected, false);
+                Transition::Stay
+            }
+            (GameState::Settings { selected }, Action::MoveRight) => {
+                adjust_setting(settings, *selected, true);
+                Transition::Stay
+            }
+            _ => Transition::Stay,
+        }
+    }
+
+    /// Text to show over the world while this state is on top
+    pub fn overlay(
+        &self,
+        settings: &Config,
+        bindings: &Bindings,
+        telemetry: Option<&Telemetry>,
+    ) -> Option<String> {
+        let key = |action| bindings.describe(action);
+        match self {
+            GameState::Title => Some(format!(
+                "rusty-ai\n\n{}: play\n{}: settings\n{}: quit",
+                key(Action::Confirm),
+                key(Action::Settings),
+                key(Action::Quit)
+            )),
+            GameState::Playing => None,
+            GameState::Paused => Some(format!(
+                "PAUSED\n\n{}: resume\n{}: quit to title\n{}/{}: rewind / forward a tick\n\n\
+                 Selected entity:\n{}/{}: speed\n{}/{}: direction",
+                key(Action::Pause),
+                key(Action::Quit),
+                key(Action::RewindBack),
+                key(Action::RewindForward),
+                key(Action::MoveUp),
+                key(Action::MoveDown),
+                key(Action::MoveLeft),
+                key(Action::MoveRight)
+            )),
+            GameState::GameOver => Some(format!(
+                "GAME OVER\n\n{}\n\n{}: play again\n{}: quit to title",
+                telemetry.map_or_else(String::new, Telemetry::summary),
+                key(Action::Confirm),
+                key(Action::Quit)
+            )),
+            GameState::Settings { selected } => {
+                let mut text = "SETTINGS\n\n".to_string();
+                for (i, name) in SETTINGS.iter().enumerate() {
+                    let cursor = if i == *selected { ">" } else { " " };
+                    text += &format!("{} {}: {}\n", cursor, name, setting_value(settings, i));
+                }
+                text += &format!(
+                    "\n{}/{}: select\n{}/{}: change\n{}: back",
+                    key(Action::MoveUp),
+                    key(Action::MoveDown),
+                    key(Action::MoveLeft),
+                    key(Action::MoveRight),
+                    key(Action::Pause)
+                );
+                Some(text)
+            }
+        }
+    }
+}
+
+fn setting_value(settings: &Config, index: usize) -> String {
+    match index {
+        0 => format!("{:?}", settings.mode),
+        1 => on_off(settings.flocking).to_string(),
+        2 => on_off(settings.line_of_sight).to_string(),
+        3 => settings.hero_health.to_string(),
+        4 => format!("{:?}", settings.hero_control),
+        _ => unreachable!(),
+    }
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value {
+        "On"
+    } else {
+        "Off"
+    }
+}
+
+/// Step the setting at `index` to its next (or previous) value
+fn adjust_setting(settings: &mut Config, index: usize, forwards: bool) {
+    match index {
+        0 => {
+            let modes = [GameMode::Hunt, GameMode::Tag, GameMode::Mixed];
+            let current = modes
+                .iter()
+                .position(|&mode| mode == settings.mode)
+                .unwrap();
+            let step = if forwards { 1 } else { modes.len() - 1 };
+            settings.mode = modes[(current + step) % modes.len()];
+        }
+        1 => settings.flocking = !settings.flocking,
+        2 => settings.line_of_sight = !settings.line_of_sight,
+        3 => {
+            settings.hero_health = match forwards {
+                true => (settings.hero_health + 1).min(MAX_HERO_HEALTH),
+                false => settings.hero_health.saturating_sub(1).max(1),
+            }
+        }
+        4 => {
+            settings.hero_control = match settings.hero_control {
+                HeroControl::AI => HeroControl::Keyboard,
+                HeroControl::Keyboard => HeroControl::AI,
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// The stack of active states. Only the top state handles input and decides what runs.
+pub struct StateStack {
+    states: Vec<GameState>,
+}
+
+impl StateStack {
+    pub fn new(initial: GameState) -> StateStack {
+        StateStack {
+            states: vec![initial],
+        }
+    }
+
+    pub fn top(&self) -> &GameState {
+        self.states.last().expect("state stack is never empty")
+    }
+
+    pub fn top_mut(&mut self) -> &mut GameState {
+        self.states.last_mut().expect("state stack is never empty")
+    }
+
+    pub fn push(&mut self, state: GameState) {
+        self.states.push(state);
+    }
+
+    /// Remove the top state, unless it's the only one left
+    pub fn pop(&mut self) {
+        if self.states.len() > 1 {
+            self.states.pop();
+        }
+    }
+
+    pub fn reset(&mut self, state: GameState) {
+        self.states = vec![state];
+    }
+}
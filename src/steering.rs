@@ -0,0 +1,246 @@
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::vector::Vector2;
+
+/// How far ahead (in ticks of travel) pursuers and evaders predict their target's position
+const MAX_PREDICTION_TICKS: f32 = 10.0;
+/// Distance from a target within which arriving agents start slowing down
+const ARRIVE_SLOWING_RADIUS: f32 = 30.0;
+/// Distance of the wander circle in front of the agent
+const WANDER_DISTANCE: f32 = 10.0;
+/// Radius of the wander circle
+const WANDER_RADIUS: f32 = 8.0;
+/// Maximum change (radians) in the wander angle per tick
+const WANDER_JITTER: f32 = 1.5;
+/// How far ahead (in ticks of travel) agents look for obstacles
+const OBSTACLE_LOOK_AHEAD_TICKS: f32 = 8.0;
+/// Extra clearance agents try to keep from obstacles
+const OBSTACLE_MARGIN: f32 = 10.0;
+/// Distance within which agents push away from each other
+const SEPARATION_RADIUS: f32 = 30.0;
+/// Distance within which flocking agents align and cohere with each other
+pub const NEIGHBOURHOOD_RADIUS: f32 = 60.0;
+
+/// Something a behaviour can steer relative to, always the nearest of its kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Target {
+    /// The nearest enemy that runs from the hero
+    Prey,
+    /// The nearest enemy that hunts the hero
+    Chaser,
+    /// The nearest hero this entity can perceive
+    Hero,
+}
+
+/// A single steering behaviour, see the functions of the same name for details
+// Not every behaviour is used by the built-in steering setups yet
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Behaviour {
+    Seek(Target),
+    Flee(Target),
+    /// Flee the target only while it's within the given distance
+    FleeWithin(Target, f32),
+    Arrive(Target),
+    Pursue(Target),
+    Evade(Target),
+    Wander,
+    AvoidObstacles,
+    Separation,
+    Alignment,
+    Cohesion,
+}
+
+/// A behaviour and how much it contributes to the combined steering force
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WeightedBehaviour {
+    pub behaviour: Behaviour,
+    pub weight: f32,
+}
+
+impl WeightedBehaviour {
+    pub fn new(behaviour: Behaviour, weight: f32) -> WeightedBehaviour {
+        WeightedBehaviour { behaviour, weight }
+    }
+}
+
+/// The kinematic state of the entity being steered
+#[derive(Debug, Clone, Copy)]
+pub struct Agent {
+    pub position: Vector2,
+    pub velocity: Vector2,
+    pub max_speed: f32,
+}
+
+/// A moving body that can be sought, fled from, pursued or evaded
+#[derive(Debug, Clone, Copy)]
+pub struct Body {
+    pub position: Vector2,
+    pub velocity: Vector2,
+}
+
+/// A circular region agents should steer around
+#[derive(Debug, Clone, Copy)]
+pub struct Circle {
+    pub center: Vector2,
+    pub radius: f32,
+}
+
+/// The acceleration required to turn the agent's velocity into `desired_velocity`
+fn steer_towards(agent: &Agent, desired_velocity: Vector2) -> Vector2 {
+    desired_velocity - agent.velocity
+}
+
+/// Head straight for the target at full speed
+pub fn seek(agent: &Agent, target: Vector2) -> Vector2 {
+    let desired_velocity = (target - agent.position).normalized() * agent.max_speed;
+    steer_towards(agent, desired_velocity)
+}
+
+/// Head straight away from the threat at full speed
+pub fn flee(agent: &Agent, threat: Vector2) -> Vector2 {
+    let desired_velocity = (agent.position - threat).normalized() * agent.max_speed;
+    steer_towards(agent, desired_velocity)
+}
+
+/// Flee the threat, but only once it comes within `radius`
+pub fn flee_within(agent: &Agent, threat: Vector2, radius: f32) -> Vector2 {
+    match agent.position.distance(threat) {
+        distance if distance > radius => Vector2::ZERO,
+        _ => flee(agent, threat),
+    }
+}
+
+/// Seek the target, slowing down on approach so the agent comes to rest on it
+pub fn arrive(agent: &Agent, target: Vector2) -> Vector2 {
+    let offset = target - agent.position;
+    let distance = offset.length();
+    let speed = match distance {
+        d if d < ARRIVE_SLOWING_RADIUS => agent.max_speed * d / ARRIVE_SLOWING_RADIUS,
+        _ => agent.max_speed,
+    };
+    // Never overshoot the target in a single tick
+    let desired_velocity = offset.normalized() * speed.min(distance);
+    steer_towards(agent, desired_velocity)
+}
+
+/// Where `body` will be once the agent could reach its current position
+fn predict(agent: &Agent, body: &Body) -> Vector2 {
+    let ticks = match agent.max_speed {
+        speed if speed > f32::EPSILON => {
+            (agent.position.distance(body.position) / speed).min(MAX_PREDICTION_TICKS)
+        }
+        _ => 0.0,
+    };
+    body.position + body.velocity * ticks
+}
+
+/// Seek the position the target is predicted to be at
+pub fn pursue(agent: &Agent, target: &Body) -> Vector2 {
+    seek(agent, predict(agent, target))
+}
+
+/// Flee from the position the threat is predicted to be at
+pub fn evade(agent: &Agent, threat: &Body) -> Vector2 {
+    flee(agent, predict(agent, threat))
+}
+
+/// Meander randomly by seeking a point that drifts around a circle in front of the agent.
+///
+/// `wander_angle` is the agent's persistent position on that circle, updated each call.
+pub fn wander(agent: &Agent, wander_angle: &mut f32, rng: &mut impl Rng) -> Vector2 {
+    *wander_angle += rng.gen_range(-WANDER_JITTER..=WANDER_JITTER);
+    let heading = match agent.velocity.normalized() {
+        heading if heading.is_zero() => Vector2::from_angle(*wander_angle),
+        heading => heading,
+    };
+    let circle_center = agent.position + heading * WANDER_DISTANCE;
+    seek(
+        agent,
+        circle_center + Vector2::from_angle(*wander_angle) * WANDER_RADIUS,
+    )
+}
+
+/// Push sideways away from the nearest obstacle in the agent's path
+pub fn avoid_obstacles(agent: &Agent, obstacles: &[Circle]) -> Vector2 {
+    let look_ahead = agent.velocity * OBSTACLE_LOOK_AHEAD_TICKS;
+    let ahead = agent.position + look_ahead;
+    let halfway = agent.position + look_ahead * 0.5;
+
+    let most_threatening = obstacles
+        .iter()
+        .filter(|obstacle| {
+            let clearance = obstacle.radius + OBSTACLE_MARGIN;
+            [agent.position, halfway, ahead]
+                .iter()
+                .any(|point| point.distance(obstacle.center) < clearance)
+        })
+        .min_by(|a, b| {
+            let a_distance = a.center.distance(agent.position);
+            let b_distance = b.center.distance(agent.position);
+            a_distance.total_cmp(&b_distance)
+        });
+
+    match most_threatening {
+        Some(obstacle) => (ahead - obstacle.center).normalized() * agent.max_speed,
+        None => Vector2::ZERO,
+    }
+}
+
+/// Push away from nearby neighbours, more strongly the closer they are
+pub fn separation(agent: &Agent, neighbours: &[Body]) -> Vector2 {
+    let push = neighbours
+        .iter()
+        .map(|neighbour| agent.position - neighbour.position)
+        .filter(|offset| !offset.is_zero() && offset.length() < SEPARATION_RADIUS)
+        .fold(Vector2::ZERO, |push, offset| {
+            push + offset.normalized() * (1.0 - offset.length() / SEPARATION_RADIUS)
+        });
+
+    match push {
+        push if push.is_zero() => Vector2::ZERO,
+        push => steer_towards(agent, push.normalized() * agent.max_speed),
+    }
+}
+
+/// Match the average heading of the neighbours
+pub fn alignment(agent: &Agent, neighbours: &[Body]) -> Vector2 {
+    let heading = neighbours
+        .iter()
+        .fold(Vector2::ZERO, |sum, neighbour| sum + neighbour.velocity);
+
+    match heading {
+        heading if heading.is_zero() => Vector2::ZERO,
+        heading => steer_towards(agent, heading.normalized() * agent.max_speed),
+    }
+}
+
+/// Seek the average position of the neighbours
+pub fn cohesion(agent: &Agent, neighbours: &[Body]) -> Vector2 {
+    if neighbours.is_empty() {
+        return Vector2::ZERO;
+    }
+    let center = neighbours
+        .iter()
+        .fold(Vector2::ZERO, |sum, neighbour| sum + neighbour.position)
+        / neighbours.len() as f32;
+    seek(agent, center)
+}
+
+/// Whether the straight line between two points passes clear of every obstacle
+pub fn line_of_sight(from: Vector2, to: Vector2, obstacles: &[Circle]) -> bool {
+    let line = to - from;
+    let length_squared = line.length_squared();
+    obstacles.iter().all(|obstacle| {
+        // The point on the line nearest the obstacle's center
+        let t = match length_squared {
+            l if l > f32::EPSILON => {
+                let to_center = obstacle.center - from;
+                (to_center.dot(line) / l).clamp(0.0, 1.0)
+            }
+            _ => 0.0,
+        };
+        (from + line * t).distance(obstacle.center) >= obstacle.radius
+    })
+}
@@ -0,0 +1,118 @@
+use serde::{Deserialize, Serialize};
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+/// A 2D vector in world space, used for positions, velocities and accelerations
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Vector2 {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Vector2 {
+    pub const ZERO: Vector2 = Vector2 { x: 0.0, y: 0.0 };
+
+    pub fn new(x: f32, y: f32) -> Vector2 {
+        Vector2 { x, y }
+    }
+
+    /// A unit vector pointing at the given angle (radians, clockwise from +x as y points down)
+    pub fn from_angle(angle: f32) -> Vector2 {
+        Vector2::new(angle.cos(), angle.sin())
+    }
+
+    /// The angle this vector points at, in the same convention as `from_angle`
+    pub fn angle(self) -> f32 {
+        self.y.atan2(self.x)
+    }
+
+    pub fn length(self) -> f32 {
+        self.length_squared().sqrt()
+    }
+
+    pub fn length_squared(self) -> f32 {
+        self.x * self.x + self.y * self.y
+    }
+
+    pub fn dot(self, other: Vector2) -> f32 {
+        self.x * other.x + self.y * other.y
+    }
+
+    pub fn distance(self, other: Vector2) -> f32 {
+        (other - self).length()
+    }
+
+    /// Returns a unit vector in the same direction, or zero for a zero-length vector
+    pub fn normalized(self) -> Vector2 {
+        let length = self.length();
+        if length > f32::EPSILON {
+            self / length
+        } else {
+            Vector2::ZERO
+        }
+    }
+
+    /// Returns this vector scaled down (if required) so its length doesn't exceed `max_length`
+    pub fn truncate(self, max_length: f32) -> Vector2 {
+        if self.length_squared() > max_length * max_length {
+            self.normalized() * max_length
+        } else {
+            self
+        }
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.length_squared() <= f32::EPSILON
+    }
+}
+
+impl Add for Vector2 {
+    type Output = Vector2;
+
+    fn add(self, other: Vector2) -> Vector2 {
+        Vector2::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl AddAssign for Vector2 {
+    fn add_assign(&mut self, other: Vector2) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Vector2 {
+    type Output = Vector2;
+
+    fn sub(self, other: Vector2) -> Vector2 {
+        Vector2::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl SubAssign for Vector2 {
+    fn sub_assign(&mut self, other: Vector2) {
+        *self = *self - other;
+    }
+}
+
+impl Mul<f32> for Vector2 {
+    type Output = Vector2;
+
+    fn mul(self, scale: f32) -> Vector2 {
+        Vector2::new(self.x * scale, self.y * scale)
+    }
+}
+
+impl Div<f32> for Vector2 {
+    type Output = Vector2;
+
+    fn div(self, scale: f32) -> Vector2 {
+        Vector2::new(self.x / scale, self.y / scale)
+    }
+}
+
+impl Neg for Vector2 {
+    type Output = Vector2;
+
+    fn neg(self) -> Vector2 {
+        Vector2::new(-self.x, -self.y)
+    }
+}